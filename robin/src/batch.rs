@@ -0,0 +1,357 @@
+use connection::Connection;
+use error::RobinResult;
+use job::{serialize_arg, Job, JobId};
+use queue_adapters::{EnqueuedJob, JobQueue, QueueIdentifier, RetryCount};
+use serde::Serialize;
+use std::fmt;
+use uuid::Uuid;
+
+/// A group of jobs that are enqueued together, with callback jobs that are enqueued once they've
+/// all finished.
+///
+/// A job in the batch has finished once it succeeds, or once it's moved to the dead queue or
+/// cancelled. Jobs that are retried haven't finished yet. The callbacks are enqueued by the queue
+/// in the same step that records the last job finishing, so they're enqueued exactly once.
+/// Uniqueness declared with [`UniqueJob`](../job/struct.UniqueJob.html) doesn't apply to jobs
+/// in a batch or to its callbacks.
+///
+/// Create one with [`Connection::batch`](../connection/struct.Connection.html#method.batch).
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # use robin::prelude::*;
+/// # use robin::memory_queue::*;
+/// jobs! {
+///     SendInvoice(u32),
+///     InvoicingDone(()),
+/// }
+/// #
+/// # impl SendInvoice {
+/// #     fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # impl InvoicingDone {
+/// #     fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+///
+/// # fn main() {
+/// # let con = robin_establish_connection!(
+/// #     MemoryQueue,
+/// #     Config::default(),
+/// #     MemoryQueueConfig::default()
+/// # ).unwrap();
+/// let mut batch = con.batch();
+/// batch.on_success(&InvoicingDone, &()).unwrap();
+/// for customer_id in 0..100 {
+///     SendInvoice::add_to_batch(&customer_id, &mut batch).unwrap();
+/// }
+/// let batch_id = batch.run().unwrap();
+/// # }
+/// ```
+pub struct Batch<'a, Q: 'a> {
+    con: &'a Connection<Q>,
+    id: BatchId,
+    jobs: Vec<(QueueIdentifier, EnqueuedJob)>,
+    callbacks: Vec<BatchCallback>,
+}
+
+impl<'a, Q> Batch<'a, Q>
+where
+    Q: JobQueue,
+{
+    #[doc(hidden)]
+    pub fn new(con: &'a Connection<Q>) -> Batch<'a, Q> {
+        Batch {
+            con,
+            id: BatchId::new(),
+            jobs: vec![],
+            callbacks: vec![],
+        }
+    }
+
+    /// Get the ID
+    pub fn id(&self) -> &BatchId {
+        &self.id
+    }
+
+    /// Add a job to the batch. The job isn't enqueued until the batch is run, when it's also
+    /// passed through the [`ClientMiddleware`](../middleware/trait.ClientMiddleware.html).
+    ///
+    /// Prefer the `add_to_batch` method generated by [`jobs!`](../macro.jobs.html), which checks
+    /// the type of the arguments.
+    pub fn perform_later<J, A>(&mut self, job: &J, args: A) -> RobinResult<JobId>
+    where
+        J: Job<Q>,
+        A: Serialize,
+    {
+        let enq_job = EnqueuedJob::new(
+            &job.name().0,
+            &serialize_arg(args)?.to_json()?,
+            RetryCount::NeverRetried,
+        ).with_batch_id(self.id.clone());
+        let id = enq_job.id().clone();
+        self.jobs.push((job.options().queue, enq_job));
+        Ok(id)
+    }
+
+    /// Enqueue `job` once all the jobs in the batch have succeeded.
+    pub fn on_success<J, A>(&mut self, job: &J, args: A) -> RobinResult<()>
+    where
+        J: Job<Q>,
+        A: Serialize,
+    {
+        self.callback(BatchEvent::Success, job, args)
+    }
+
+    /// Enqueue `job` once all the jobs in the batch have finished, whether they succeeded or not.
+    pub fn on_complete<J, A>(&mut self, job: &J, args: A) -> RobinResult<()>
+    where
+        J: Job<Q>,
+        A: Serialize,
+    {
+        self.callback(BatchEvent::Complete, job, args)
+    }
+
+    /// Enqueue all the jobs in the batch. Returns the ID of the batch, which can be used to get
+    /// its status with
+    /// [`Connection::batch_status`](../connection/struct.Connection.html#method.batch_status).
+    ///
+    /// The callbacks are passed through the client middleware now, rather than when they're
+    /// enqueued. The callbacks of an empty batch are enqueued right away.
+    pub fn run(self) -> RobinResult<BatchId> {
+        self.con.enqueue_batch(&self.id, self.jobs, self.callbacks)?;
+        Ok(self.id)
+    }
+
+    fn callback<J, A>(&mut self, event: BatchEvent, job: &J, args: A) -> RobinResult<()>
+    where
+        J: Job<Q>,
+        A: Serialize,
+    {
+        let enq_job = EnqueuedJob::new(
+            &job.name().0,
+            &serialize_arg(args)?.to_json()?,
+            RetryCount::NeverRetried,
+        );
+        self.callbacks
+            .push(BatchCallback::new(event, job.options().queue, enq_job));
+        Ok(())
+    }
+}
+
+impl<'a, Q> fmt::Debug for Batch<'a, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Batch {{ id: {:?}, jobs: {:?}, callbacks: {:?} }}",
+            self.id,
+            self.jobs.len(),
+            self.callbacks
+        )
+    }
+}
+
+/// The unique ID given to a batch when it's created.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BatchId(String);
+
+impl BatchId {
+    /// Generate a new random ID.
+    pub fn new() -> BatchId {
+        BatchId(Uuid::new_v4().hyphenated().to_string())
+    }
+
+    /// Get the ID as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for BatchId {
+    fn default() -> BatchId {
+        BatchId::new()
+    }
+}
+
+impl fmt::Display for BatchId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<T> From<T> for BatchId
+where
+    T: Into<String>,
+{
+    fn from(t: T) -> BatchId {
+        BatchId(t.into())
+    }
+}
+
+/// When a batch callback is enqueued.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BatchEvent {
+    /// Once all the jobs in the batch have succeeded.
+    Success,
+
+    /// Once all the jobs in the batch have finished, whether they succeeded or not.
+    Complete,
+}
+
+/// A job to enqueue once a batch has finished. The job is created when the batch is run and
+/// stored with the batch, so the queue can enqueue it without calling back into Robin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchCallback {
+    event: BatchEvent,
+    queue: QueueIdentifier,
+    job: EnqueuedJob,
+}
+
+impl BatchCallback {
+    /// Create a callback that enqueues `job` into `queue` on `event`.
+    pub fn new(event: BatchEvent, queue: QueueIdentifier, job: EnqueuedJob) -> BatchCallback {
+        BatchCallback { event, queue, job }
+    }
+
+    /// Get the event the callback is enqueued on
+    pub fn event(&self) -> BatchEvent {
+        self.event
+    }
+
+    /// Get the queue the callback job is enqueued into
+    pub fn queue(&self) -> QueueIdentifier {
+        self.queue
+    }
+
+    /// Get the callback job
+    pub fn job(&self) -> &EnqueuedJob {
+        &self.job
+    }
+
+    /// Get the name of the callback job
+    pub fn name(&self) -> &str {
+        self.job.name()
+    }
+
+    /// Get the arguments of the callback job
+    pub fn args(&self) -> &str {
+        self.job.args()
+    }
+
+    /// `true` if the callback should be enqueued for a batch with the given status.
+    pub fn fires_for(&self, status: &BatchStatus) -> bool {
+        if !status.is_finished() {
+            return false;
+        }
+
+        match self.event {
+            BatchEvent::Success => status.failed() == 0,
+            BatchEvent::Complete => true,
+        }
+    }
+}
+
+/// How many of the jobs in a batch have finished.
+///
+/// Get it with
+/// [`Connection::batch_status`](../connection/struct.Connection.html#method.batch_status).
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BatchStatus {
+    total: usize,
+    pending: usize,
+    succeeded: usize,
+    failed: usize,
+}
+
+impl BatchStatus {
+    /// Create the status of a batch where none of the `total` jobs have finished yet.
+    pub fn new(total: usize) -> BatchStatus {
+        BatchStatus {
+            total,
+            pending: total,
+            succeeded: 0,
+            failed: 0,
+        }
+    }
+
+    /// Create a status from its counts.
+    pub fn from_counts(total: usize, pending: usize, succeeded: usize, failed: usize) -> BatchStatus {
+        BatchStatus {
+            total,
+            pending,
+            succeeded,
+            failed,
+        }
+    }
+
+    /// Get the number of jobs in the batch
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Get the number of jobs that haven't finished yet
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Get the number of jobs that succeeded
+    pub fn succeeded(&self) -> usize {
+        self.succeeded
+    }
+
+    /// Get the number of jobs that were moved to the dead queue or cancelled
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// `true` if all the jobs in the batch have finished.
+    pub fn is_finished(&self) -> bool {
+        self.pending == 0
+    }
+
+    /// Record that one of the pending jobs finished. Returns `None` if no jobs were pending.
+    pub fn job_finished(self, succeeded: bool) -> Option<BatchStatus> {
+        if self.pending == 0 {
+            return None;
+        }
+
+        let mut status = BatchStatus {
+            pending: self.pending - 1,
+            ..self
+        };
+        if succeeded {
+            status.succeeded += 1;
+        } else {
+            status.failed += 1;
+        }
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callbacks_fire_once_the_batch_has_finished() {
+        let job = EnqueuedJob::new("Done", "{}", RetryCount::NeverRetried);
+        let success = BatchCallback::new(BatchEvent::Success, QueueIdentifier::Main, job.clone());
+        let complete = BatchCallback::new(BatchEvent::Complete, QueueIdentifier::Main, job);
+
+        let status = BatchStatus::new(2).job_finished(true).unwrap();
+        assert!(!success.fires_for(&status));
+        assert!(!complete.fires_for(&status));
+
+        let succeeded = status.job_finished(true).unwrap();
+        assert!(success.fires_for(&succeeded));
+        assert!(complete.fires_for(&succeeded));
+
+        let failed = status.job_finished(false).unwrap();
+        assert!(!success.fires_for(&failed));
+        assert!(complete.fires_for(&failed));
+
+        assert_eq!(failed.job_finished(true), None);
+    }
+}
@@ -0,0 +1,173 @@
+use connection::Connection;
+use error::RobinResult;
+use job::{serialize_arg, Args, Job, JobId, JobName, TypedJob, UniqueJob};
+use queue_adapters::{JobQueue, QueueIdentifier};
+use serde::Serialize;
+use serde_json;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A job followed by jobs that are enqueued one after the other, each once the previous one has
+/// succeeded.
+///
+/// If a job in the chain ends up in the dead queue, the jobs after it are never enqueued.
+/// Start a chain with the `chain` method generated by [`jobs!`](../macro.jobs.html).
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # use robin::prelude::*;
+/// # use robin::memory_queue::*;
+/// jobs! {
+///     DownloadVideo(String),
+///     TranscodeVideo(String),
+///     NotifyUser(u32),
+/// }
+/// #
+/// # impl DownloadVideo {
+/// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # impl TranscodeVideo {
+/// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # impl NotifyUser {
+/// #     fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+///
+/// # fn main() {
+/// # let con = robin_establish_connection!(
+/// #     MemoryQueue,
+/// #     Config::default(),
+/// #     MemoryQueueConfig::default()
+/// # ).unwrap();
+/// let url = "https://example.com/video.mp4".to_string();
+/// DownloadVideo::chain(&url)
+///     .then(&TranscodeVideo, &url)
+///     .then(&NotifyUser, &1)
+///     .perform_later(&con)
+///     .unwrap();
+/// # }
+/// ```
+pub struct Chain<Q> {
+    queue: QueueIdentifier,
+    unique: Option<UniqueJob>,
+    jobs: RobinResult<Vec<Continuation>>,
+    queue_type: PhantomData<Q>,
+}
+
+impl<Q> Chain<Q>
+where
+    Q: JobQueue,
+{
+    /// Start a chain with `job`.
+    ///
+    /// Prefer the `chain` method generated by [`jobs!`](../macro.jobs.html), which checks the
+    /// type of the arguments.
+    pub fn new<J, A>(job: &J, args: A) -> Chain<Q>
+    where
+        J: Job<Q>,
+        A: Serialize,
+    {
+        let options = job.options();
+        Chain {
+            queue: options.queue,
+            unique: options.unique,
+            jobs: Continuation::new::<Q, _, _>(job, args).map(|first| vec![first]),
+            queue_type: PhantomData,
+        }
+    }
+
+    /// Enqueue `job` once the previous job in the chain has succeeded. The arguments must have
+    /// the type declared for the job in [`jobs!`](../macro.jobs.html):
+    ///
+    /// ```compile_fail
+    /// # #[macro_use]
+    /// # extern crate robin;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// # use robin::prelude::*;
+    /// # use robin::memory_queue::*;
+    /// jobs! {
+    ///     DownloadVideo(String),
+    ///     NotifyUser(u32),
+    /// }
+    /// #
+    /// # impl DownloadVideo {
+    /// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+    /// # }
+    /// # impl NotifyUser {
+    /// #     fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult { Ok(()) }
+    /// # }
+    ///
+    /// # fn main() {
+    /// let url = "https://example.com/video.mp4".to_string();
+    /// let chain: Chain<MemoryQueue> = DownloadVideo::chain(&url).then(&NotifyUser, &url);
+    /// # }
+    /// ```
+    pub fn then<J, A>(self, job: &J, args: &A) -> Chain<Q>
+    where
+        J: Job<Q> + TypedJob<A>,
+        A: Serialize,
+    {
+        let jobs = self.jobs.and_then(|mut jobs| {
+            jobs.push(Continuation::new::<Q, _, _>(job, args)?);
+            Ok(jobs)
+        });
+
+        Chain { jobs, ..self }
+    }
+
+    /// Put the first job of the chain into its queue. Returns the ID of the enqueued job.
+    ///
+    /// Fails if any of the arguments in the chain couldn't be serialized. Also fails with
+    /// `Error::DuplicateChain` if the chain is unique and an identical first job is already
+    /// queued or running, since the rest of the chain can't be attached to that job.
+    pub fn perform_later(self, con: &Connection<Q>) -> RobinResult<JobId> {
+        let mut jobs = self.jobs?;
+        let first = jobs.remove(0);
+        con.enqueue_chain(self.queue, first, jobs, self.unique)
+    }
+}
+
+impl<Q> fmt::Debug for Chain<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Chain {{ queue: {:?}, unique: {:?}, jobs: {:?} }}",
+            self.queue, self.unique, self.jobs
+        )
+    }
+}
+
+/// A job that's enqueued once the job it's attached to has succeeded.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Continuation {
+    name: String,
+    args: String,
+}
+
+impl Continuation {
+    /// Create a continuation that performs `job` with `args`.
+    pub fn new<Q, J, A>(job: &J, args: A) -> RobinResult<Continuation>
+    where
+        J: Job<Q>,
+        A: Serialize,
+    {
+        Ok(Continuation {
+            name: job.name().0,
+            args: serialize_arg(args)?.to_json()?,
+        })
+    }
+
+    /// Get the name of the job
+    pub fn name(&self) -> JobName {
+        JobName::from(self.name.as_str())
+    }
+
+    /// Get the arguments of the job
+    pub fn args(&self) -> RobinResult<Args> {
+        serde_json::from_str(&self.args).map_err(From::from)
+    }
+}
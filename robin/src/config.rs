@@ -1,5 +1,14 @@
+use job::JobResult;
+use metrics::Metrics;
+use middleware::Middleware;
 use num_cpus;
+use periodic::PeriodicJob;
+use queue_adapters::{EnqueuedJob, QueueIdentifier};
+use rand::{self, Rng};
 use std::default::Default;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
 
 /// Configuration options used throughout Robin.
 ///
@@ -15,15 +24,110 @@ use std::default::Default;
 /// assert_eq!(config.worker_count, 10);
 /// # }
 /// ```
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Config {
-    /// The maximum number of times a job will be retried. After that it will discarded.
+    /// The maximum number of times a job will be retried. After that it will be moved to the
+    /// dead queue.
+    ///
+    /// Jobs can override this, and the other retry settings, with a
+    /// [`RetryPolicy`](struct.RetryPolicy.html) in [`jobs!`](../macro.jobs.html).
     pub retry_count_limit: u32,
 
     /// The number of worker threads to spawn. Each thread will have its own Redis
     /// connection, so make sure you have enough connections available.
     /// Defaults to the number of CPUs your machine has.
+    ///
+    /// These workers perform jobs from the main queue and from the named queues in
+    /// [`queues`](#structfield.queues) that have a weight.
     pub worker_count: usize,
+
+    /// Settings for the named queues jobs can be put into with `jobs!`. Jobs in a named queue
+    /// are only performed if the queue is listed here.
+    /// Defaults to no named queues.
+    ///
+    /// ```rust
+    /// # use robin::prelude::*;
+    /// use robin::queue_adapters::QueueIdentifier;
+    ///
+    /// # fn main() {
+    /// let mut config = Config::default();
+    /// config.queues = vec![
+    ///     // Checked before the main queue by the shared workers and has two workers of its own
+    ///     QueueSettings::new(QueueIdentifier::Named("payments")).weight(10).workers(2),
+    ///     // Only performed by its own worker
+    ///     QueueSettings::new(QueueIdentifier::Named("exports")).weight(0).workers(1),
+    /// ];
+    /// # }
+    /// ```
+    pub queues: Vec<QueueSettings>,
+
+    /// How the workers from [`worker_count`](#structfield.worker_count) pick which queue to
+    /// fetch the next job from.
+    /// Defaults to [`FetchStrategy::StrictPriority`](enum.FetchStrategy.html).
+    pub fetch_strategy: FetchStrategy,
+
+    /// How often the worker checks for scheduled jobs that are due to be performed.
+    /// Defaults to one second.
+    pub scheduled_poll_interval: Duration,
+
+    /// How long to wait before retrying a failed job.
+    /// Defaults to the same curve as Sidekiq, see [`RetryBackoff`](enum.RetryBackoff.html).
+    pub retry_backoff: RetryBackoff,
+
+    /// How long [`worker::boot`](../worker/fn.boot.html) waits for the jobs being performed to
+    /// finish when the process is told to shut down.
+    /// Defaults to 25 seconds.
+    pub shutdown_timeout: Duration,
+
+    /// Called with jobs the workers don't know how to perform, for example because the job type
+    /// has been renamed. If the handler returns `Ok` the job is considered handled, otherwise the
+    /// job is moved to the dead queue.
+    /// Defaults to `None` which moves all unknown jobs to the dead queue.
+    pub unknown_job_handler: Option<UnknownJobHandler>,
+
+    /// How long the status of a job is kept after it last changed. Setting this enables
+    /// [`Connection::job_status`](../connection/struct.Connection.html#method.job_status) and
+    /// [`Connection::cancel`](../connection/struct.Connection.html#method.cancel).
+    /// Defaults to `None` which means no status is recorded.
+    pub job_status_ttl: Option<Duration>,
+
+    /// How long the status of a batch is kept once all its jobs have finished.
+    /// Defaults to 24 hours.
+    pub batch_ttl: Duration,
+
+    /// The jobs to enqueue periodically. See
+    /// [`PeriodicJob`](../periodic/struct.PeriodicJob.html) for more info.
+    /// Defaults to no jobs.
+    pub periodic_jobs: Vec<PeriodicJob>,
+
+    /// How long jobs can take to perform before they're considered failed, unless they declare
+    /// their own timeout in [`jobs!`](../macro.jobs.html). Jobs that time out fail with
+    /// [`JobTimedOut`](../error/struct.JobTimedOut.html) and are retried or moved to the dead
+    /// queue like other failed jobs.
+    ///
+    /// Jobs with a timeout are performed on a separate thread. If the job times out the thread
+    /// is left behind and replaced with a new one, since threads can't be interrupted. The job
+    /// keeps running until it finishes on its own, so a retried copy can be performed while the
+    /// original is still running. A job with a
+    /// [`ConcurrencyLimit`](../job/struct.ConcurrencyLimit.html) keeps its slot until the
+    /// original finishes. Defaults to `None` which means jobs can take as long as they want.
+    pub job_timeout: Option<Duration>,
+
+    /// The middleware that wraps enqueuing and performing jobs. See
+    /// [`Middleware`](../middleware/struct.Middleware.html) for more info.
+    /// Defaults to no middleware.
+    pub middleware: Middleware,
+
+    /// The sinks that receive metrics about jobs and queues. See
+    /// [`MetricsSink`](../metrics/trait.MetricsSink.html) for more info.
+    /// Defaults to no sinks.
+    pub metrics: Metrics,
+
+    /// How often the workers record a heartbeat in the queue backend. Processes that miss three
+    /// heartbeats in a row are no longer listed by
+    /// [`Connection::workers`](../connection/struct.Connection.html#method.workers).
+    /// Defaults to five seconds.
+    pub heartbeat_interval: Duration,
 }
 
 impl Default for Config {
@@ -31,6 +135,461 @@ impl Default for Config {
         Config {
             retry_count_limit: 10,
             worker_count: num_cpus::get(),
+            queues: vec![],
+            fetch_strategy: FetchStrategy::StrictPriority,
+            scheduled_poll_interval: Duration::from_secs(1),
+            retry_backoff: RetryBackoff::default(),
+            shutdown_timeout: Duration::from_secs(25),
+            unknown_job_handler: None,
+            job_status_ttl: None,
+            batch_ttl: Duration::from_secs(60 * 60 * 24),
+            periodic_jobs: vec![],
+            job_timeout: None,
+            middleware: Middleware::new(),
+            metrics: Metrics::new(),
+            heartbeat_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Config {
+    /// The retry policy for jobs that don't declare their own.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(self.retry_count_limit).backoff(self.retry_backoff)
+    }
+
+    /// The queues the workers from [`worker_count`](#structfield.worker_count) fetch from,
+    /// together with their weights. The main queue has a weight of 1 unless it's listed in
+    /// [`queues`](#structfield.queues).
+    pub fn weighted_queues(&self) -> Vec<(QueueIdentifier, u32)> {
+        let mut queues = vec![];
+
+        if !self.queues.iter().any(|q| q.queue == QueueIdentifier::Main) {
+            queues.push((QueueIdentifier::Main, 1));
+        }
+
+        queues.extend(
+            self.queues
+                .iter()
+                .filter(|q| q.weight > 0)
+                .map(|q| (q.queue, q.weight)),
+        );
+
+        queues
+    }
+
+    /// All the queues the workers might put jobs into or take jobs from.
+    pub fn queue_identifiers(&self) -> Vec<QueueIdentifier> {
+        let mut idens = vec![
+            QueueIdentifier::Main,
+            QueueIdentifier::Retry,
+            QueueIdentifier::Dead,
+        ];
+
+        for settings in &self.queues {
+            if !idens.contains(&settings.queue) {
+                idens.push(settings.queue);
+            }
+        }
+
+        idens
+    }
+}
+
+/// How a named queue is processed. See [`Config::queues`](struct.Config.html#structfield.queues).
+#[derive(Debug, Clone, Copy)]
+pub struct QueueSettings {
+    /// The queue the settings are for.
+    pub queue: QueueIdentifier,
+
+    /// How much the workers from [`Config::worker_count`](struct.Config.html#structfield.worker_count)
+    /// favor this queue. A weight of 0 means they never fetch from it.
+    /// Defaults to 1.
+    pub weight: u32,
+
+    /// The number of workers that only perform jobs from this queue.
+    /// Defaults to 0.
+    pub workers: usize,
+}
+
+impl QueueSettings {
+    /// Create the default settings for a queue.
+    pub fn new(queue: QueueIdentifier) -> QueueSettings {
+        QueueSettings {
+            queue,
+            weight: 1,
+            workers: 0,
+        }
+    }
+
+    /// Set the weight.
+    pub fn weight(self, weight: u32) -> QueueSettings {
+        QueueSettings { weight, ..self }
+    }
+
+    /// Set the number of dedicated workers.
+    pub fn workers(self, workers: usize) -> QueueSettings {
+        QueueSettings { workers, ..self }
+    }
+}
+
+/// The strategies for picking which queue to fetch the next job from.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FetchStrategy {
+    /// Always fetch from the queue with the highest weight that has jobs. Queues with a lower
+    /// weight are only fetched from once the queues above them are empty.
+    StrictPriority,
+
+    /// Pick the queue at random, with the chance of each queue being picked proportional to its
+    /// weight. No queue is starved, but higher weights are favored.
+    Weighted,
+}
+
+impl FetchStrategy {
+    /// The order in which to check the queues for the next fetch.
+    pub fn order(&self, queues: &[(QueueIdentifier, u32)]) -> Vec<QueueIdentifier> {
+        let mut queues = queues.to_vec();
+
+        match *self {
+            FetchStrategy::StrictPriority => {
+                queues.sort_by(|a, b| b.1.cmp(&a.1));
+                queues.into_iter().map(|(queue, _)| queue).collect()
+            }
+
+            FetchStrategy::Weighted => {
+                let mut rng = rand::thread_rng();
+                let mut order = Vec::with_capacity(queues.len());
+
+                while !queues.is_empty() {
+                    let total: u32 = queues.iter().map(|&(_, weight)| weight.max(1)).sum();
+                    let mut pick = rng.gen_range(0, total);
+                    let idx = queues
+                        .iter()
+                        .position(|&(_, weight)| {
+                            if pick < weight.max(1) {
+                                true
+                            } else {
+                                pick -= weight.max(1);
+                                false
+                            }
+                        })
+                        .unwrap_or(0);
+                    order.push(queues.remove(idx).0);
+                }
+
+                order
+            }
+        }
+    }
+}
+
+/// A function that handles jobs the workers don't know how to perform.
+/// See [`Config::unknown_job_handler`](struct.Config.html#structfield.unknown_job_handler).
+///
+/// ```rust
+/// # use robin::prelude::*;
+/// use robin::queue_adapters::EnqueuedJob;
+///
+/// fn log_unknown_job(enq_job: &EnqueuedJob) -> JobResult {
+///     println!("Dropping unknown job {}", enq_job.name());
+///     Ok(())
+/// }
+///
+/// # fn main() {
+/// let mut config = Config::default();
+/// config.unknown_job_handler = Some(UnknownJobHandler(log_unknown_job));
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct UnknownJobHandler(pub fn(&EnqueuedJob) -> JobResult);
+
+impl fmt::Debug for UnknownJobHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UnknownJobHandler")
+    }
+}
+
+/// How a failed job is retried.
+///
+/// Declare it for a job in [`jobs!`](../macro.jobs.html). Jobs that don't have one use
+/// [`Config::retry_policy`](struct.Config.html#method.retry_policy).
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # use robin::prelude::*;
+/// use std::error::Error;
+/// use std::io;
+///
+/// fn is_network_error(err: &(Error + 'static)) -> bool {
+///     err.downcast_ref::<io::Error>().is_some()
+/// }
+///
+/// jobs! {
+///     SyncAccount(u32) => {
+///         retry: RetryPolicy::new(25).retry_on(is_network_error),
+///     },
+/// }
+/// #
+/// # impl SyncAccount {
+/// #     fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    /// The maximum number of times the job will be retried. After that it will be moved to the
+    /// dead queue.
+    pub retry_count_limit: u32,
+
+    /// How long to wait before retrying the job.
+    pub backoff: RetryBackoff,
+
+    /// Decides if the job should be retried after failing with the given error. Jobs that aren't
+    /// retried are moved to the dead queue right away.
+    /// Defaults to retrying on all errors.
+    pub retry_on: fn(&(Error + 'static)) -> bool,
+}
+
+impl RetryPolicy {
+    /// Create a policy that retries on all errors up to `retry_count_limit` times, with the
+    /// default backoff.
+    pub fn new(retry_count_limit: u32) -> RetryPolicy {
+        RetryPolicy {
+            retry_count_limit,
+            backoff: RetryBackoff::default(),
+            retry_on: retry_on_all_errors,
+        }
+    }
+
+    /// Create a policy that never retries. Failed jobs are moved to the dead queue right away.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy::new(0).retry_on(retry_on_no_errors)
+    }
+
+    /// Set the backoff.
+    pub fn backoff(self, backoff: RetryBackoff) -> RetryPolicy {
+        RetryPolicy { backoff, ..self }
+    }
+
+    /// Only retry when the error matches `retry_on`.
+    pub fn retry_on(self, retry_on: fn(&(Error + 'static)) -> bool) -> RetryPolicy {
+        RetryPolicy { retry_on, ..self }
+    }
+
+    /// `true` if a job that failed with `err` should be retried, assuming the retry limit hasn't
+    /// been reached.
+    pub fn should_retry(&self, err: &(Error + 'static)) -> bool {
+        (self.retry_on)(err)
+    }
+}
+
+fn retry_on_all_errors(_: &(Error + 'static)) -> bool {
+    true
+}
+
+fn retry_on_no_errors(_: &(Error + 'static)) -> bool {
+    false
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RetryPolicy {{ retry_count_limit: {:?}, backoff: {:?} }}",
+            self.retry_count_limit, self.backoff
+        )
+    }
+}
+
+/// The strategies for how long to wait before retrying a failed job.
+///
+/// ```rust
+/// # use robin::prelude::*;
+/// use std::time::Duration;
+///
+/// # fn main() {
+/// let backoff = RetryBackoff::Constant(Duration::from_secs(5));
+///
+/// assert_eq!(backoff.delay(1), Duration::from_secs(5));
+/// assert_eq!(backoff.delay(2), Duration::from_secs(5));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub enum RetryBackoff {
+    /// Retry the job right away.
+    Immediate,
+
+    /// Wait the same amount of time before each retry.
+    Constant(Duration),
+
+    /// Wait `retry_count ^ exponent` seconds plus `base`, plus a random jitter of up to
+    /// `jitter * retry_count`. The delay is capped at a year.
+    ///
+    /// The jitter prevents jobs that failed at the same time from all being retried at the same
+    /// time.
+    Exponential {
+        /// The exponent the retry count is raised to.
+        exponent: u32,
+
+        /// The minimum amount of time to wait.
+        base: Duration,
+
+        /// The maximum amount of random jitter added per retry.
+        jitter: Duration,
+    },
+}
+
+impl RetryBackoff {
+    /// How long to wait before performing a job that has failed `retry_count` times.
+    pub fn delay(&self, retry_count: u32) -> Duration {
+        match *self {
+            RetryBackoff::Immediate => Duration::from_secs(0),
+
+            RetryBackoff::Constant(delay) => delay,
+
+            RetryBackoff::Exponential {
+                exponent,
+                base,
+                jitter,
+            } => {
+                let max_delay = Duration::from_secs(MAX_RETRY_DELAY_IN_SECS);
+                let delay = u64::from(retry_count).saturating_pow(exponent);
+                let jitter = jitter.checked_mul(retry_count).unwrap_or(max_delay);
+
+                Duration::from_secs(delay.min(MAX_RETRY_DELAY_IN_SECS))
+                    .checked_add(base)
+                    .and_then(|delay| delay.checked_add(random_duration_up_to(jitter)))
+                    .map_or(max_delay, |delay| delay.min(max_delay))
+            }
+        }
+    }
+}
+
+/// The longest `RetryBackoff::Exponential` waits before retrying a job.
+const MAX_RETRY_DELAY_IN_SECS: u64 = 60 * 60 * 24 * 365;
+
+impl Default for RetryBackoff {
+    /// `retry_count ^ 4 + 15s + jitter`, which is the same curve as Sidekiq uses.
+    fn default() -> RetryBackoff {
+        RetryBackoff::Exponential {
+            exponent: 4,
+            base: Duration::from_secs(15),
+            jitter: Duration::from_secs(10),
+        }
+    }
+}
+
+fn random_duration_up_to(max: Duration) -> Duration {
+    let max_millis = max.as_secs()
+        .saturating_mul(1_000)
+        .saturating_add(u64::from(max.subsec_nanos() / 1_000_000));
+
+    if max_millis == 0 {
+        Duration::from_secs(0)
+    } else {
+        Duration::from_millis(rand::thread_rng().gen_range(0, max_millis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_predicate() {
+        use std::io;
+
+        fn is_io_error(err: &(Error + 'static)) -> bool {
+            err.downcast_ref::<io::Error>().is_some()
         }
+
+        let policy = RetryPolicy::new(25).retry_on(is_io_error);
+        let io_error = io::Error::new(io::ErrorKind::Other, "timeout");
+        let fmt_error = fmt::Error;
+
+        assert!(policy.should_retry(&io_error));
+        assert!(!policy.should_retry(&fmt_error));
+        assert!(!RetryPolicy::never().should_retry(&fmt_error));
+    }
+
+    #[test]
+    fn test_strict_priority_order() {
+        let queues = vec![
+            (QueueIdentifier::Main, 1),
+            (QueueIdentifier::Named("payments"), 10),
+            (QueueIdentifier::Named("exports"), 1),
+        ];
+
+        assert_eq!(
+            FetchStrategy::StrictPriority.order(&queues),
+            vec![
+                QueueIdentifier::Named("payments"),
+                QueueIdentifier::Main,
+                QueueIdentifier::Named("exports"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weighted_order() {
+        let queues = vec![
+            (QueueIdentifier::Main, 1),
+            (QueueIdentifier::Named("payments"), 9),
+        ];
+
+        let payments_first = (0..1000)
+            .filter(|_| FetchStrategy::Weighted.order(&queues)[0] == queues[1].0)
+            .count();
+
+        assert!(payments_first > 800);
+        assert!(payments_first < 1000);
+    }
+
+    #[test]
+    fn test_immediate_backoff() {
+        assert_eq!(RetryBackoff::Immediate.delay(3), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let backoff = RetryBackoff::Exponential {
+            exponent: 4,
+            base: Duration::from_secs(15),
+            jitter: Duration::from_secs(10),
+        };
+
+        for _ in 0..100 {
+            let delay = backoff.delay(2);
+            assert!(delay >= Duration::from_secs(16 + 15));
+            assert!(delay < Duration::from_secs(16 + 15 + 20));
+        }
+    }
+
+    #[test]
+    fn test_exponential_backoff_without_jitter() {
+        let backoff = RetryBackoff::Exponential {
+            exponent: 2,
+            base: Duration::from_secs(0),
+            jitter: Duration::from_secs(0),
+        };
+
+        assert_eq!(backoff.delay(3), Duration::from_secs(9));
+    }
+
+    #[test]
+    fn test_exponential_backoff_is_capped() {
+        let year = Duration::from_secs(60 * 60 * 24 * 365);
+
+        assert_eq!(RetryBackoff::default().delay(u32::max_value()), year);
+
+        let backoff = RetryBackoff::Exponential {
+            exponent: 1,
+            base: Duration::new(u64::max_value(), 0),
+            jitter: Duration::new(u64::max_value(), 0),
+        };
+        assert_eq!(backoff.delay(2), year);
     }
 }
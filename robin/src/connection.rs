@@ -1,8 +1,20 @@
-use config::Config;
+use batch::{Batch, BatchCallback, BatchId, BatchStatus};
+use chain::Continuation;
+use config::{Config, RetryPolicy};
 use error::*;
+use heartbeat::WorkerInfo;
 use job::*;
+use job_status::{JobState, JobStatus};
+use metrics::MetricsSink;
+use periodic::PeriodicJob;
+use query::JobQuery;
 use queue_adapters::{redis_queue::RedisQueue, EnqueuedJob, JobQueue, NoJobDequeued,
-                     QueueIdentifier, RetryCount};
+                     PoisonedJob, QueueIdentifier, RetryCount};
+use serde_json;
+use std::time::{Duration, SystemTime};
+
+/// How many jobs `Connection::list_jobs` reads from the queue at a time.
+const LIST_JOBS_CHUNK_SIZE: usize = 1_000;
 
 /// Create a new connection.
 ///
@@ -24,9 +36,8 @@ where
     Q: JobQueue<Config = K>,
 {
     JobQueue::new(&queue_config)
-        .map(|(main_queue, retry_queue)| Connection {
-            main_queue: main_queue,
-            retry_queue: retry_queue,
+        .map(|queue| Connection {
+            queue: queue,
             config: config,
             lookup_job: Box::new(lookup_job),
         })
@@ -37,8 +48,7 @@ where
 #[allow(missing_debug_implementations)]
 pub struct Connection<Q> {
     config: Config,
-    main_queue: Q,
-    retry_queue: Q,
+    queue: Q,
     lookup_job: Box<LookupJob<Q>>,
 }
 
@@ -61,68 +71,826 @@ where
         name: JobName,
         args: &Args,
         retry_count: RetryCount,
+        unique: Option<UniqueJob>,
+    ) -> RobinResult<JobId> {
+        let enq_job = EnqueuedJob::new(&name.0, &args.to_json()?, retry_count);
+        self.enqueue_job(iden, enq_job, name, args, unique)
+    }
+
+    #[doc(hidden)]
+    pub fn enqueue_chain(
+        &self,
+        iden: QueueIdentifier,
+        first: Continuation,
+        rest: Vec<Continuation>,
+        unique: Option<UniqueJob>,
+    ) -> RobinResult<JobId> {
+        let name = first.name();
+        let args = first.args()?;
+        let enq_job = EnqueuedJob::new(&name.0, &args.to_json()?, RetryCount::NeverRetried)
+            .with_continuations(rest);
+        self.enqueue_job(iden, enq_job, name, &args, unique)
+    }
+
+    /// Enqueue the next job in the chain of a job that succeeded, if there is one.
+    #[doc(hidden)]
+    pub fn enqueue_continuation(&self, enq_job: &EnqueuedJob) -> RobinResult<()> {
+        let (next, rest) = match enq_job.continuations().split_first() {
+            Some(continuations) => continuations,
+            None => return Ok(()),
+        };
+
+        let options = self.lookup_job(&next.name())
+            .map_or_else(JobOptions::default, |job| job.options());
+        match self.enqueue_chain(
+            options.queue,
+            next.clone(),
+            rest.to_vec(),
+            options.unique,
+        ) {
+            Ok(_) => Ok(()),
+            // Retrying the job wouldn't help, since the identical job will most likely still be
+            // there. The rest of the chain is dropped.
+            Err(Error::DuplicateChain(existing_id)) => {
+                warn!(
+                    "Skipped \"{}\" after job {}, since the identical job {} is already queued",
+                    next.name().0,
+                    enq_job.id(),
+                    existing_id
+                );
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn enqueue_job(
+        &self,
+        iden: QueueIdentifier,
+        enq_job: EnqueuedJob,
+        name: JobName,
+        args: &Args,
+        unique: Option<UniqueJob>,
+    ) -> RobinResult<JobId> {
+        self.config
+            .middleware
+            .enqueue(enq_job, args, &mut |enq_job: EnqueuedJob| {
+                let is_chain = !enq_job.continuations().is_empty();
+                let enq_job = match self.lock_unique(enq_job, &name, args, unique)? {
+                    Ok(enq_job) => enq_job,
+                    // The rest of the chain would be lost, since it can't be attached to the
+                    // job that's already queued
+                    Err(existing_id) => if is_chain {
+                        return Err(Error::DuplicateChain(existing_id));
+                    } else {
+                        return Ok(existing_id);
+                    },
+                };
+                let id = enq_job.id().clone();
+
+                debug!("Enqueued \"{}\" ({}) with {}", name.0, id, args.json());
+                if let Err(err) = self.queue.enqueue(iden, enq_job.clone()) {
+                    self.release_unique(&enq_job);
+                    return Err(Error::from(err));
+                }
+                self.record_status(&id, &JobStatus::new(JobState::Enqueued));
+                self.config.metrics.job_enqueued(&name.0, iden);
+                Ok(id)
+            })
+    }
+
+    #[doc(hidden)]
+    pub fn enqueue_many_to(
+        &self,
+        iden: QueueIdentifier,
+        name: JobName,
+        many_args: &[Args],
+        retry_count: RetryCount,
+        unique: Option<UniqueJob>,
+    ) -> RobinResult<Vec<JobId>> {
+        let mut ids = Vec::with_capacity(many_args.len());
+        let mut enq_jobs = Vec::with_capacity(many_args.len());
+
+        let new_jobs = many_args
+            .iter()
+            .map(|args| Ok(EnqueuedJob::new(&name.0, &args.to_json()?, retry_count)))
+            .collect::<RobinResult<Vec<_>>>()?;
+
+        for (enq_job, args) in new_jobs.into_iter().zip(many_args) {
+            let result = self.config
+                .middleware
+                .enqueue(enq_job, args, &mut |enq_job: EnqueuedJob| {
+                    match self.lock_unique(enq_job, &name, args, unique)? {
+                        Ok(enq_job) => {
+                            let id = enq_job.id().clone();
+                            enq_jobs.push(enq_job);
+                            Ok(id)
+                        }
+                        Err(existing_id) => Ok(existing_id),
+                    }
+                });
+
+            match result {
+                Ok(id) => ids.push(id),
+                Err(err) => {
+                    self.release_all_unique(&enq_jobs);
+                    return Err(err);
+                }
+            }
+        }
+
+        debug!("Enqueued {} \"{}\" jobs", enq_jobs.len(), name.0);
+        if let Err(err) = self.queue.enqueue_many(iden, enq_jobs.clone()) {
+            self.release_all_unique(&enq_jobs);
+            return Err(Error::from(err));
+        }
+        self.record_statuses(&enq_jobs, &JobStatus::new(JobState::Enqueued));
+        for _ in &enq_jobs {
+            self.config.metrics.job_enqueued(&name.0, iden);
+        }
+        Ok(ids)
+    }
+
+    #[doc(hidden)]
+    pub fn enqueue_at(
+        &self,
+        iden: QueueIdentifier,
+        name: JobName,
+        args: &Args,
+        retry_count: RetryCount,
+        time: SystemTime,
+        unique: Option<UniqueJob>,
+    ) -> RobinResult<JobId> {
+        let enq_job = EnqueuedJob::new(&name.0, &args.to_json()?, retry_count);
+        self.config
+            .middleware
+            .enqueue(enq_job, args, &mut |enq_job: EnqueuedJob| {
+                let enq_job = match self.lock_unique(enq_job, &name, args, unique)? {
+                    Ok(enq_job) => enq_job,
+                    Err(existing_id) => return Ok(existing_id),
+                };
+                let id = enq_job.id().clone();
+
+                debug!(
+                    "Scheduled \"{}\" ({}) with {} at {:?}",
+                    name.0,
+                    id,
+                    args.json(),
+                    time
+                );
+                if let Err(err) = self.queue.enqueue_at(iden, enq_job.clone(), time) {
+                    self.release_unique(&enq_job);
+                    return Err(Error::from(err));
+                }
+                self.record_status(&id, &JobStatus::new(JobState::Scheduled));
+                self.config.metrics.job_enqueued(&name.0, iden);
+                Ok(id)
+            })
+    }
+
+    /// Move the scheduled jobs that are due onto their queues. Returns the number of jobs that
+    /// were moved.
+    ///
+    /// The workers spawned by [`spawn_workers`](../worker/fn.spawn_workers.html) do this for you,
+    /// so normally you wouldn't need to call this.
+    pub fn enqueue_due_jobs(&self) -> RobinResult<usize> {
+        let now = SystemTime::now();
+        let mut count = 0;
+        for iden in self.config.queue_identifiers() {
+            count += self.queue.enqueue_due_jobs(iden, now)?;
+        }
+        Ok(count)
+    }
+
+    /// Enqueue the runs of the periodic jobs in the config that are due at `now`. Returns the
+    /// number of jobs that were enqueued.
+    ///
+    /// The first time a periodic job is seen its runs start from `now`. Runs that another process
+    /// has already claimed are skipped.
+    ///
+    /// The workers spawned by [`spawn_workers`](../worker/fn.spawn_workers.html) do this for you,
+    /// so normally you wouldn't need to call this.
+    pub fn enqueue_periodic_jobs(&self, now: SystemTime) -> RobinResult<usize> {
+        let mut count = 0;
+        for periodic_job in &self.config.periodic_jobs {
+            count += self.enqueue_periodic_job(periodic_job, now)?;
+        }
+        Ok(count)
+    }
+
+    #[doc(hidden)]
+    pub fn retry(&self, enq_job: EnqueuedJob, policy: &RetryPolicy) -> RobinResult<()> {
+        let delay = enq_job.retry_count().delay(policy);
+        let enq_job = enq_job.requeued();
+
+        let failures = match *enq_job.retry_count() {
+            RetryCount::NeverRetried => 0,
+            RetryCount::Count(n) => n,
+        };
+        self.update_status(enq_job.id(), |status| {
+            status.finish_attempt(JobState::Retrying(failures), enq_job.last_error())
+        });
+
+        debug!(
+            "Re-enqueued \"{}\" ({}) with {}",
+            enq_job.name(),
+            enq_job.id(),
+            enq_job.args()
+        );
+        self.config.metrics.job_retried(enq_job.name());
+        let iden = self.job_queue(enq_job.name());
+        if delay == Duration::from_secs(0) {
+            self.queue.enqueue(iden, enq_job).map_err(Error::from)
+        } else {
+            let time = SystemTime::now() + delay;
+            self.queue.enqueue_at(iden, enq_job, time).map_err(Error::from)
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn acquire_rate_limit(&self, name: &JobName, rate_limit: &RateLimit) -> Option<Duration> {
+        let key = rate_limit.key(name);
+        match self.queue
+            .acquire_rate_limit(&key, rate_limit.limit, rate_limit.period)
+        {
+            Ok(wait) => wait,
+            // Failing closed like the concurrency limit, since performing the job could exceed
+            // the limit
+            Err(err) => {
+                error!("Failed to check the rate limit \"{}\"\n{:?}", key, err);
+                Some(self.config.scheduled_poll_interval)
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn acquire_concurrency_slot(
+        &self,
+        name: &JobName,
+        concurrency: &ConcurrencyLimit,
+        enq_job: &EnqueuedJob,
+    ) -> bool {
+        let key = concurrency.key(name);
+        match self.queue.acquire_concurrency_slot(
+            &key,
+            enq_job.id(),
+            concurrency.limit,
+            concurrency.lease,
+        ) {
+            Ok(acquired) => acquired,
+            // Failing closed, since performing the job could exceed the limit
+            Err(err) => {
+                error!("Failed to check the concurrency limit \"{}\"\n{:?}", key, err);
+                false
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn release_concurrency_slot(
+        &self,
+        name: &JobName,
+        concurrency: &ConcurrencyLimit,
+        enq_job: &EnqueuedJob,
+    ) {
+        let key = concurrency.key(name);
+        if let Err(err) = self.queue.release_concurrency_slot(&key, enq_job.id()) {
+            error!(
+                "Failed to release the slot of job {} in the concurrency limit \"{}\"\n{:?}",
+                enq_job.id(),
+                key,
+                err
+            );
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn throttle(
+        &self,
+        iden: QueueIdentifier,
+        enq_job: EnqueuedJob,
+        wait: Duration,
+    ) -> RobinResult<()> {
+        debug!(
+            "Postponed \"{}\" ({}), performing it in {:?}",
+            enq_job.name(),
+            enq_job.id(),
+            wait
+        );
+        self.update_status(enq_job.id(), |status| status.transition(JobState::Scheduled));
+        self.queue
+            .enqueue_at(iden, enq_job.requeued(), SystemTime::now() + wait)
+            .map_err(Error::from)
+    }
+
+    #[doc(hidden)]
+    pub fn put_back(&self, iden: QueueIdentifier, enq_job: EnqueuedJob) -> RobinResult<()> {
+        debug!(
+            "Put \"{}\" ({}) back into its queue",
+            enq_job.name(),
+            enq_job.id()
+        );
+        self.queue.put_back(iden, enq_job).map_err(Error::from)
+    }
+
+    #[doc(hidden)]
+    pub fn bury(&self, enq_job: EnqueuedJob) -> RobinResult<()> {
+        warn!(
+            "Moved \"{}\" with {} to the dead queue",
+            enq_job.name(),
+            enq_job.args()
+        );
+        self.update_status(enq_job.id(), |status| {
+            status.finish_attempt(JobState::Dead, enq_job.last_error())
+        });
+        self.release_unique(&enq_job);
+        self.config.metrics.job_dead(enq_job.name());
+        self.queue.enqueue(QueueIdentifier::Dead, enq_job.clone())?;
+
+        // The job is already in the dead queue, so failing to record it in its batch mustn't
+        // fail burying it
+        if let Err(err) = self.batch_job_finished(&enq_job, false) {
+            error!(
+                "Failed to record that job {} finished in its batch\n{:?}",
+                enq_job.id(),
+                err
+            );
+        }
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    pub fn release_unique(&self, enq_job: &EnqueuedJob) {
+        if let Some(key) = enq_job.unique_key() {
+            if let Err(err) = self.queue.unlock_unique(key, enq_job.id()) {
+                error!(
+                    "Failed to release the unique lock of job {}\n{:?}",
+                    enq_job.id(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Record that a job in a batch finished. The queue enqueues the callbacks of the batch if it
+    /// was the last one. Does nothing for jobs that aren't in a batch, or that were already
+    /// recorded as finished.
+    #[doc(hidden)]
+    pub fn batch_job_finished(&self, enq_job: &EnqueuedJob, succeeded: bool) -> RobinResult<()> {
+        let id = match enq_job.batch_id() {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+
+        let finished =
+            self.queue
+                .finish_batch_job(id, enq_job.id(), succeeded, self.config.batch_ttl)?;
+        if let Some((status, callbacks)) = finished {
+            if status.is_finished() {
+                debug!("Batch {} finished", id);
+            }
+            self.batch_callbacks_enqueued(id, &callbacks);
+        }
+        Ok(())
+    }
+
+    fn release_all_unique(&self, enq_jobs: &[EnqueuedJob]) {
+        for enq_job in enq_jobs {
+            self.release_unique(enq_job);
+        }
+    }
+
+    /// Record that a job is being performed. Returns `false` if the job was cancelled, in which
+    /// case it must not be performed.
+    #[doc(hidden)]
+    pub fn job_started(&self, enq_job: &EnqueuedJob) -> bool {
+        let ttl = match self.config.job_status_ttl {
+            Some(ttl) => ttl,
+            None => return true,
+        };
+
+        let result = self.change_status(enq_job.id(), ttl, |status| {
+            let status = status.unwrap_or_else(|| JobStatus::new(JobState::Enqueued));
+            if status.state() == JobState::Cancelled {
+                None
+            } else {
+                Some(status.start_attempt())
+            }
+        });
+
+        match result {
+            Ok(started) => started.is_some(),
+            Err(err) => {
+                error!(
+                    "Failed to update the status of job {}\n{:?}",
+                    enq_job.id(),
+                    err
+                );
+                true
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn job_succeeded(&self, enq_job: &EnqueuedJob) {
+        self.update_status(enq_job.id(), |status| {
+            status.finish_attempt(JobState::Succeeded, None)
+        });
+    }
+
+    /// Start a new batch of jobs. See [`Batch`](../batch/struct.Batch.html) for more info.
+    pub fn batch(&self) -> Batch<Q> {
+        Batch::new(self)
+    }
+
+    /// The status of a batch, or `None` if it isn't known. Batches are forgotten once
+    /// [`Config::batch_ttl`](../config/struct.Config.html#structfield.batch_ttl) has passed
+    /// since all their jobs finished.
+    pub fn batch_status(&self, id: &BatchId) -> RobinResult<Option<BatchStatus>> {
+        self.queue.batch_status(id).map_err(Error::from)
+    }
+
+    #[doc(hidden)]
+    pub fn enqueue_batch(
+        &self,
+        id: &BatchId,
+        jobs: Vec<(QueueIdentifier, EnqueuedJob)>,
+        callbacks: Vec<BatchCallback>,
     ) -> RobinResult<()> {
-        let enq_job = EnqueuedJob::build()
-            .name(name.0.clone())
-            .args(args.to_json()?)
-            .retry_count(retry_count)
-            .done();
+        // Run through the client middleware before the batch is created, so it only counts the
+        // jobs the middleware let through
+        let mut accepted = Vec::with_capacity(jobs.len());
+        for (iden, enq_job) in jobs {
+            let args: Args = serde_json::from_str(enq_job.args())?;
+            self.config
+                .middleware
+                .enqueue(enq_job, &args, &mut |enq_job: EnqueuedJob| {
+                    let id = enq_job.id().clone();
+                    accepted.push((iden, enq_job));
+                    Ok(id)
+                })?;
+        }
+        let jobs = accepted;
+
+        let mut accepted = Vec::with_capacity(callbacks.len());
+        for callback in callbacks {
+            let args: Args = serde_json::from_str(callback.args())?;
+            let (event, iden) = (callback.event(), callback.queue());
+            self.config.middleware.enqueue(
+                callback.job().clone(),
+                &args,
+                &mut |enq_job: EnqueuedJob| {
+                    let id = enq_job.id().clone();
+                    accepted.push(BatchCallback::new(event, iden, enq_job));
+                    Ok(id)
+                },
+            )?;
+        }
+        let callbacks = accepted;
+
+        if jobs.is_empty() {
+            let status = BatchStatus::new(0);
+            let mut enqueued = vec![];
+            for callback in callbacks.into_iter().filter(|c| c.fires_for(&status)) {
+                self.queue.enqueue(callback.queue(), callback.job().clone())?;
+                enqueued.push(callback.job().clone());
+            }
+            self.batch_callbacks_enqueued(id, &enqueued);
+            return Ok(());
+        }
+
+        let job_ids: Vec<JobId> = jobs.iter().map(|&(_, ref enq_job)| enq_job.id().clone()).collect();
+        self.queue.create_batch(id, &job_ids, &callbacks)?;
+
+        let mut enqueued = Vec::with_capacity(jobs.len());
+        let mut jobs = jobs.into_iter().peekable();
+        while let Some((iden, enq_job)) = jobs.next() {
+            let mut enq_jobs = vec![enq_job];
+            while jobs.peek().map_or(false, |&(next_iden, _)| next_iden == iden) {
+                enq_jobs.extend(jobs.next().map(|(_, enq_job)| enq_job));
+            }
+
+            if let Err(err) = self.queue.enqueue_many(iden, enq_jobs.clone()) {
+                self.abandon_batch(id, &enqueued);
+                return Err(Error::from(err));
+            }
+            enqueued.extend(enq_jobs.into_iter().map(|enq_job| (iden, enq_job)));
+        }
 
-        match iden {
-            QueueIdentifier::Main => {
-                debug!("Enqueued \"{}\" with {}", name.0, args.json());
-                self.main_queue.enqueue(enq_job).map_err(Error::from)
+        debug!("Enqueued batch {} with {} jobs", id, enqueued.len());
+        for &(iden, ref enq_job) in &enqueued {
+            self.record_status(enq_job.id(), &JobStatus::new(JobState::Enqueued));
+            self.config.metrics.job_enqueued(enq_job.name(), iden);
+        }
+        Ok(())
+    }
+
+    /// Undo a batch whose jobs couldn't all be enqueued, by removing the jobs that were and
+    /// deleting the batch. Jobs that a worker already picked up are performed without the
+    /// batch.
+    fn abandon_batch(&self, id: &BatchId, enqueued: &[(QueueIdentifier, EnqueuedJob)]) {
+        for &(iden, ref enq_job) in enqueued {
+            if let Err(err) = self.queue.remove(iden, enq_job) {
+                error!(
+                    "Failed to remove job {} of abandoned batch {}\n{:?}",
+                    enq_job.id(),
+                    id,
+                    err
+                );
             }
-            QueueIdentifier::Retry => {
-                debug!("Re-enqueued \"{}\" with {}", name.0, args.json());
-                self.retry_queue.enqueue(enq_job).map_err(Error::from)
+        }
+
+        if let Err(err) = self.queue.delete_batch(id) {
+            error!("Failed to delete abandoned batch {}\n{:?}", id, err);
+        }
+    }
+
+    /// The status of a job, or `None` if it isn't known.
+    ///
+    /// Statuses are only recorded if
+    /// [`Config::job_status_ttl`](../config/struct.Config.html#structfield.job_status_ttl) is
+    /// set, and are forgotten once the TTL has passed since they last changed.
+    pub fn job_status(&self, id: &JobId) -> RobinResult<Option<JobStatus>> {
+        self.queue.job_status(id).map_err(Error::from)
+    }
+
+    /// Cancel a job so it won't be performed. Only jobs that are waiting to be performed can be
+    /// cancelled.
+    ///
+    /// Returns `false` if the job is already being performed, has finished, or its status isn't
+    /// known. That includes when
+    /// [`Config::job_status_ttl`](../config/struct.Config.html#structfield.job_status_ttl) isn't
+    /// set.
+    pub fn cancel(&self, id: &JobId) -> RobinResult<bool> {
+        let ttl = match self.config.job_status_ttl {
+            Some(ttl) => ttl,
+            None => return Ok(false),
+        };
+
+        let cancelled = self.change_status(id, ttl, |status| match status {
+            Some(ref status) if status.state().is_waiting() => {
+                Some(status.clone().transition(JobState::Cancelled))
             }
+            _ => None,
+        })?;
+
+        if cancelled.is_some() {
+            debug!("Cancelled job {}", id);
         }
+        Ok(cancelled.is_some())
+    }
+
+    #[doc(hidden)]
+    pub fn quarantine(&self, enq_job: EnqueuedJob) -> RobinResult<()> {
+        warn!(
+            "Received unknown job \"{}\" with {}",
+            enq_job.name(),
+            enq_job.args()
+        );
+
+        let reason = match self.config.unknown_job_handler {
+            None => format!("Unknown job \"{}\"", enq_job.name()),
+            Some(handler) => match (handler.0)(&enq_job) {
+                Ok(()) => {
+                    self.release_unique(&enq_job);
+                    return self.batch_job_finished(&enq_job, false);
+                }
+                Err(err) => format!(
+                    "Unknown job \"{}\" wasn't handled: {}",
+                    enq_job.name(),
+                    err
+                ),
+            },
+        };
+
+        self.bury(enq_job.with_error(reason))
     }
 
     #[doc(hidden)]
-    pub fn retry(&self, name: JobName, args: &Args, retry_count: RetryCount) -> RobinResult<()> {
-        self.enqueue_to(QueueIdentifier::Retry, name, args, retry_count)
+    pub fn poison(&self, poisoned_job: PoisonedJob) -> RobinResult<()> {
+        warn!(
+            "Moved malformed payload to the poison queue ({:?}: {})\n{}",
+            poisoned_job.reason(),
+            poisoned_job.error(),
+            poisoned_job.payload()
+        );
+        self.queue.poison(poisoned_job).map_err(Error::from)
     }
 
     #[doc(hidden)]
     pub fn dequeue_from<'a>(
         &'a self,
+        idens: &[QueueIdentifier],
+    ) -> Result<(QueueIdentifier, Box<Job<Q> + Send>, EnqueuedJob), NoJobDequeued> {
+        let (iden, enq_job) = self.queue.dequeue(idens)?;
+
+        let name = JobName::from(enq_job.name());
+        match self.lookup_job(&name) {
+            Some(job) => Ok((iden, job, enq_job)),
+            None => Err(NoJobDequeued::BecauseUnknownJob(iden, enq_job)),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn ack(&self, iden: QueueIdentifier) -> RobinResult<()> {
+        self.queue.ack(iden).map_err(Error::from)
+    }
+
+    /// Put jobs that were being performed by workers that have crashed back into their queues.
+    /// Returns the number of jobs that were recovered.
+    ///
+    /// Only has an effect if the queue backend keeps track of jobs being performed, such as
+    /// [`RedisQueue`](../redis_queue/struct.RedisQueue.html) with `reliable_fetch` enabled.
+    /// [`spawn_workers`](../worker/fn.spawn_workers.html) calls this when starting.
+    pub fn recover_orphaned_jobs(&self) -> RobinResult<usize> {
+        let mut count = 0;
+        for iden in self.config.queue_identifiers() {
+            count += self.queue.recover(iden)?;
+        }
+        Ok(count)
+    }
+
+    /// List the jobs in a queue that match the query, in the order they'll be dequeued, without
+    /// removing them.
+    ///
+    /// The queue is read in chunks, so jobs that are enqueued or dequeued while it's being read
+    /// might be skipped or listed twice.
+    ///
+    /// Entries that can't be decoded as jobs are skipped. They're moved to the poison queue once
+    /// a worker dequeues them.
+    ///
+    /// ```rust
+    /// # #[macro_use]
+    /// # extern crate robin;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// # use robin::prelude::*;
+    /// # use robin::memory_queue::*;
+    /// use robin::queue_adapters::QueueIdentifier;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Email {
+    ///     user_id: u32,
+    /// }
+    ///
+    /// jobs! {
+    ///     SendEmail(Email),
+    /// }
+    /// #
+    /// # impl SendEmail {
+    /// #     fn perform<Q>(_args: Email, _con: &Connection<Q>) -> JobResult { Ok(()) }
+    /// # }
+    ///
+    /// # fn main() {
+    /// # let con = robin_establish_connection!(
+    /// #     MemoryQueue,
+    /// #     Config::default(),
+    /// #     MemoryQueueConfig::default()
+    /// # ).unwrap();
+    /// SendEmail::perform_later(&Email { user_id: 1 }, &con).unwrap();
+    /// SendEmail::perform_later(&Email { user_id: 2 }, &con).unwrap();
+    ///
+    /// let query = JobQuery::new().name("SendEmail").args_match("/user_id", 2);
+    /// let jobs = con.list_jobs(QueueIdentifier::Main, &query).unwrap();
+    /// assert_eq!(jobs.len(), 1);
+    /// # }
+    /// ```
+    pub fn list_jobs(
+        &self,
         iden: QueueIdentifier,
-    ) -> Result<(Box<Job<Q> + Send>, String, RetryCount), NoJobDequeued> {
-        let enq_job = match iden {
-            QueueIdentifier::Main => self.main_queue.dequeue(),
-            QueueIdentifier::Retry => self.retry_queue.dequeue(),
-        }?;
+        query: &JobQuery,
+    ) -> RobinResult<Vec<EnqueuedJob>> {
+        if query.matches_all() {
+            return self.queue
+                .jobs_range(iden, query.offset, query.limit)
+                .map_err(Error::from);
+        }
+
+        let mut jobs = vec![];
+        let mut skipped = 0;
+        let mut start = 0;
+        // Chunks are counted in entries rather than jobs, since malformed entries are left out
+        let size = self.size(iden)?;
+
+        while jobs.len() < query.limit && start < size {
+            let chunk = self.queue.jobs_range(iden, start, LIST_JOBS_CHUNK_SIZE)?;
 
-        let args = enq_job.args().to_string();
-        let name = enq_job.name().to_string();
+            for enq_job in chunk.into_iter().filter(|enq_job| query.matches(enq_job)) {
+                if skipped < query.offset {
+                    skipped += 1;
+                } else if jobs.len() < query.limit {
+                    jobs.push(enq_job);
+                }
+            }
+
+            start += LIST_JOBS_CHUNK_SIZE;
+        }
 
-        let job = self.lookup_job(&JobName::from(name.clone()))
-            .ok_or_else(move || NoJobDequeued::BecauseUnknownJob(JobName(name)))?;
+        Ok(jobs)
+    }
 
-        Ok((job, args, enq_job.retry_count().clone()))
+    /// The jobs in the dead queue, in the order they died.
+    pub fn dead_jobs(&self) -> RobinResult<Vec<EnqueuedJob>> {
+        self.queue
+            .jobs(QueueIdentifier::Dead)
+            .map_err(Error::from)
     }
 
-    /// Delete all jobs from main queue
+    /// Move a job from the dead queue back into the queue declared for it in
+    /// [`jobs!`](../macro.jobs.html), or the main queue. The retry count of the job is reset. The
+    /// job is moved atomically, so it's never lost if moving it fails.
+    ///
+    /// Returns `false` if the job wasn't in the dead queue.
+    pub fn requeue_dead_job(&self, enq_job: &EnqueuedJob) -> RobinResult<bool> {
+        let moved = self.queue.move_job(
+            QueueIdentifier::Dead,
+            enq_job,
+            self.job_queue(enq_job.name()),
+            enq_job.clone().reset(),
+        )?;
+        if !moved {
+            return Ok(false);
+        }
+
+        self.update_status(enq_job.id(), |status| status.transition(JobState::Enqueued));
+        Ok(true)
+    }
+
+    /// Move all jobs from the dead queue back into their queues. The retry counts of the jobs are
+    /// reset.
+    ///
+    /// Returns the number of jobs that were moved.
+    pub fn requeue_all_dead_jobs(&self) -> RobinResult<usize> {
+        let mut count = 0;
+        for enq_job in self.dead_jobs()? {
+            if self.requeue_dead_job(&enq_job)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// The payloads that couldn't be turned into jobs, oldest first.
+    pub fn poisoned_jobs(&self) -> RobinResult<Vec<PoisonedJob>> {
+        self.queue.poisoned_jobs().map_err(Error::from)
+    }
+
+    /// Delete all the payloads that couldn't be turned into jobs
+    pub fn delete_all_poisoned(&self) -> RobinResult<()> {
+        self.queue.delete_all_poisoned()?;
+        Ok(())
+    }
+
+    /// Delete all jobs from main queue, including the scheduled ones
     pub fn delete_all_from_main(&self) -> RobinResult<()> {
-        self.main_queue.delete_all()?;
+        self.queue.delete_all(QueueIdentifier::Main)?;
         Ok(())
     }
 
-    /// Delete all jobs from retry queue
+    /// Delete all jobs from retry queue, including the scheduled ones
     pub fn delete_all_from_retry(&self) -> RobinResult<()> {
-        self.retry_queue.delete_all()?;
+        self.queue.delete_all(QueueIdentifier::Retry)?;
+        Ok(())
+    }
+
+    /// Delete all jobs from dead queue
+    pub fn delete_all_from_dead(&self) -> RobinResult<()> {
+        self.queue.delete_all(QueueIdentifier::Dead)?;
         Ok(())
     }
 
     /// Delete all jobs from all queues
     pub fn delete_all(&self) -> RobinResult<()> {
-        self.delete_all_from_main()?;
-        self.delete_all_from_retry()?;
+        for iden in self.config.queue_identifiers() {
+            self.queue.delete_all(iden)?;
+        }
+        self.delete_all_poisoned()?;
         Ok(())
     }
 
+    /// The processes running workers that are alive, according to their heartbeats. See
+    /// [`WorkerInfo`](../heartbeat/struct.WorkerInfo.html) for what's recorded.
+    pub fn workers(&self) -> RobinResult<Vec<WorkerInfo>> {
+        self.queue.workers().map_err(Error::from)
+    }
+
+    #[doc(hidden)]
+    pub fn heartbeat(&self, info: &WorkerInfo) -> RobinResult<()> {
+        self.queue
+            .heartbeat(info, self.config.heartbeat_interval * 3)
+            .map_err(Error::from)
+    }
+
+    #[doc(hidden)]
+    pub fn remove_worker(&self, info: &WorkerInfo) -> RobinResult<()> {
+        self.queue.remove_worker(info.id()).map_err(Error::from)
+    }
+
+    /// The number of jobs in a queue
+    pub fn queue_size(&self, iden: QueueIdentifier) -> RobinResult<usize> {
+        self.size(iden)
+    }
+
     /// The number of jobs in the main queue
     pub fn main_queue_size(&self) -> RobinResult<usize> {
         self.size(QueueIdentifier::Main)
@@ -133,6 +901,21 @@ where
         self.size(QueueIdentifier::Retry)
     }
 
+    /// The number of jobs in the dead queue
+    pub fn dead_queue_size(&self) -> RobinResult<usize> {
+        self.size(QueueIdentifier::Dead)
+    }
+
+    /// The number of jobs scheduled to be put into the main queue
+    pub fn main_queue_scheduled_size(&self) -> RobinResult<usize> {
+        self.scheduled_size(QueueIdentifier::Main)
+    }
+
+    /// The number of jobs scheduled to be put into the retry queue
+    pub fn retry_queue_scheduled_size(&self) -> RobinResult<usize> {
+        self.scheduled_size(QueueIdentifier::Retry)
+    }
+
     /// `true` if there are 0 jobs in the main queue, `false` otherwise
     pub fn is_main_queue_empty(&self) -> RobinResult<bool> {
         self.is_empty(QueueIdentifier::Main)
@@ -143,20 +926,188 @@ where
         self.is_empty(QueueIdentifier::Retry)
     }
 
+    /// `true` if there are 0 jobs in the dead queue, `false` otherwise
+    pub fn is_dead_queue_empty(&self) -> RobinResult<bool> {
+        self.is_empty(QueueIdentifier::Dead)
+    }
+
+    /// Take the unique lock for the job, if it's unique. Returns the ID of the job holding the
+    /// lock if it's already taken.
+    fn lock_unique(
+        &self,
+        enq_job: EnqueuedJob,
+        name: &JobName,
+        args: &Args,
+        unique: Option<UniqueJob>,
+    ) -> RobinResult<Result<EnqueuedJob, JobId>> {
+        let unique = match unique {
+            Some(unique) => unique,
+            None => return Ok(Ok(enq_job)),
+        };
+
+        let key = unique.lock_key(name, args);
+        match self.queue.lock_unique(&key, enq_job.id(), unique.ttl)? {
+            None => Ok(Ok(enq_job.with_unique_key(key))),
+            Some(existing_id) => {
+                debug!(
+                    "Didn't enqueue \"{}\" since the identical job {} is queued or running",
+                    name.0, existing_id
+                );
+                Ok(Err(existing_id))
+            }
+        }
+    }
+
+    fn enqueue_periodic_job(
+        &self,
+        periodic_job: &PeriodicJob,
+        now: SystemTime,
+    ) -> RobinResult<usize> {
+        let key = periodic_job.key();
+        let last_run = match self.queue.last_periodic_run(&key)? {
+            Some(last_run) => last_run,
+            None => {
+                self.queue.claim_periodic_run(&key, None, now)?;
+                return Ok(0);
+            }
+        };
+
+        let name = periodic_job.name();
+        let args: Args = serde_json::from_str(periodic_job.args())?;
+        let options = self.lookup_job(&name)
+            .map_or_else(JobOptions::default, |job| job.options());
+
+        let mut previous = last_run;
+        let mut count = 0;
+        for run in periodic_job.due_runs(last_run, now) {
+            if !self.queue.claim_periodic_run(&key, Some(previous), run)? {
+                break;
+            }
+
+            debug!("Enqueuing periodic job \"{}\" for {:?}", name.0, run);
+            let enqueued = self.enqueue_to(
+                options.queue,
+                JobName::from(name.0.as_str()),
+                &args,
+                RetryCount::NeverRetried,
+                options.unique,
+            );
+
+            // Give the run back so it's enqueued on the next tick, by this or another process
+            if let Err(err) = enqueued {
+                let rollback = self.queue.claim_periodic_run(&key, Some(run), previous);
+                if let Err(rollback_err) = rollback {
+                    error!(
+                        "Failed to give back the run of periodic job \"{}\" for {:?}: {}",
+                        name.0, run, rollback_err
+                    );
+                }
+                return Err(err);
+            }
+
+            previous = run;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Record the status of the callback jobs the queue enqueued for a finished batch.
+    fn batch_callbacks_enqueued(&self, id: &BatchId, callbacks: &[EnqueuedJob]) {
+        for enq_job in callbacks {
+            debug!("Batch {} finished, enqueued \"{}\"", id, enq_job.name());
+            let options = self.lookup_job(&JobName::from(enq_job.name()))
+                .map_or_else(JobOptions::default, |job| job.options());
+            self.record_status(enq_job.id(), &JobStatus::new(JobState::Enqueued));
+            self.config.metrics.job_enqueued(enq_job.name(), options.queue);
+        }
+    }
+
+    fn record_status(&self, id: &JobId, status: &JobStatus) {
+        let ttl = match self.config.job_status_ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+
+        if let Err(err) = self.queue.set_job_status(id, status, ttl) {
+            error!("Failed to record the status of job {}\n{:?}", id, err);
+        }
+    }
+
+    fn record_statuses(&self, enq_jobs: &[EnqueuedJob], status: &JobStatus) {
+        let ttl = match self.config.job_status_ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+
+        let ids: Vec<JobId> = enq_jobs.iter().map(|enq_job| enq_job.id().clone()).collect();
+        if let Err(err) = self.queue.set_job_statuses(&ids, status, ttl) {
+            error!("Failed to record the status of {} jobs\n{:?}", ids.len(), err);
+        }
+    }
+
+    /// Change the recorded status of a job. Jobs without a status are treated as enqueued.
+    fn update_status<F>(&self, id: &JobId, f: F)
+    where
+        F: Fn(JobStatus) -> JobStatus,
+    {
+        let ttl = match self.config.job_status_ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+
+        let result = self.change_status(id, ttl, |status| {
+            Some(f(status.unwrap_or_else(|| JobStatus::new(JobState::Enqueued))))
+        });
+        if let Err(err) = result {
+            error!("Failed to update the status of job {}\n{:?}", id, err);
+        }
+    }
+
+    /// Atomically change the status of a job. `f` gets the current status, if it's known, and
+    /// returns the new one, or `None` to leave it as is. If the status changes while `f` runs,
+    /// `f` is called again with the new status.
+    ///
+    /// Returns the status that was stored, or `None` if it was left as is.
+    fn change_status<F>(&self, id: &JobId, ttl: Duration, f: F) -> RobinResult<Option<JobStatus>>
+    where
+        F: Fn(Option<JobStatus>) -> Option<JobStatus>,
+    {
+        loop {
+            let current = self.queue.job_status(id)?;
+            let status = match f(current.clone()) {
+                Some(status) => status,
+                None => return Ok(None),
+            };
+
+            if self.queue
+                .swap_job_status(id, current.as_ref(), &status, ttl)?
+            {
+                return Ok(Some(status));
+            }
+        }
+    }
+
     fn lookup_job(&self, name: &JobName) -> Option<Box<Job<Q> + Send>> {
         self.lookup_job.lookup(name)
     }
 
+    /// The queue declared for the job with the given name, or the main queue if the job isn't
+    /// known.
+    fn job_queue(&self, name: &str) -> QueueIdentifier {
+        self.lookup_job(&JobName::from(name))
+            .map_or(QueueIdentifier::Main, |job| job.options().queue)
+    }
+
     fn is_empty(&self, iden: QueueIdentifier) -> RobinResult<bool> {
         self.size(iden).map(|size| size == 0)
     }
 
     fn size(&self, iden: QueueIdentifier) -> RobinResult<usize> {
-        match iden {
-            QueueIdentifier::Main => &self.main_queue,
-            QueueIdentifier::Retry => &self.retry_queue,
-        }.size()
-            .map_err(Error::from)
+        self.queue.size(iden).map_err(Error::from)
+    }
+
+    fn scheduled_size(&self, iden: QueueIdentifier) -> RobinResult<usize> {
+        self.queue.scheduled_size(iden).map_err(Error::from)
     }
 }
 
@@ -1,6 +1,7 @@
+use job::JobId;
 use queue_adapters::{JobQueueError, JobQueueErrorInformation};
 use serde_json;
-use std::{error, fmt};
+use std::{any::Any, error, fmt, time::Duration};
 
 /// The result type used throughout Robin.
 pub type RobinResult<T> = Result<T, Error>;
@@ -16,6 +17,13 @@ pub enum Error {
 
     /// Some serialization/deserialization failed
     SerdeError(serde_json::Error),
+
+    /// The cron expression of a periodic job couldn't be parsed
+    InvalidCronExpression(String),
+
+    /// The first job of a chain is unique and the identical job with this ID is already queued
+    /// or running, so the chain wasn't enqueued
+    DuplicateChain(JobId),
 }
 
 impl fmt::Display for Error {
@@ -30,6 +38,8 @@ impl error::Error for Error {
             &Error::JobFailed(ref err) => err.description(),
             &Error::JobQueueError(ref err) => err.description(),
             &Error::SerdeError(ref err) => err.description(),
+            &Error::InvalidCronExpression(_) => "invalid cron expression",
+            &Error::DuplicateChain(_) => "identical unique job already queued",
         }
     }
 }
@@ -45,3 +55,90 @@ impl From<Box<JobQueueErrorInformation>> for Error {
         Error::JobQueueError(e)
     }
 }
+
+/// The error a job fails with when it panics while being performed.
+#[derive(Debug, Clone)]
+pub struct JobPanicked {
+    message: String,
+}
+
+impl JobPanicked {
+    /// Create a `JobPanicked` from the payload of a panic, as returned by
+    /// [`std::panic::catch_unwind`](https://doc.rust-lang.org/std/panic/fn.catch_unwind.html).
+    pub fn from_payload(payload: Box<Any + Send>) -> JobPanicked {
+        let message = if let Some(msg) = payload.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "Box<Any>".to_string()
+        };
+
+        JobPanicked { message }
+    }
+
+    /// The message the job panicked with.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for JobPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Job panicked: {}", self.message)
+    }
+}
+
+impl error::Error for JobPanicked {
+    fn description(&self) -> &str {
+        "job panicked"
+    }
+}
+
+/// The error a job fails with when it takes longer than its timeout to perform.
+#[derive(Debug, Clone, Copy)]
+pub struct JobTimedOut {
+    timeout: Duration,
+}
+
+impl JobTimedOut {
+    /// Create a `JobTimedOut` for a job that didn't finish within `timeout`.
+    pub fn new(timeout: Duration) -> JobTimedOut {
+        JobTimedOut { timeout }
+    }
+
+    /// The timeout the job didn't finish within.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl fmt::Display for JobTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Job timed out after {:?}", self.timeout)
+    }
+}
+
+impl error::Error for JobTimedOut {
+    fn description(&self) -> &str {
+        "job timed out"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic;
+
+    #[test]
+    fn test_job_panicked_from_str_and_string_payloads() {
+        let payload = panic::catch_unwind(|| panic!("boom")).unwrap_err();
+        assert_eq!(JobPanicked::from_payload(payload).message(), "boom");
+
+        let payload = panic::catch_unwind(|| panic!("{} {}", "formatted", "boom")).unwrap_err();
+        assert_eq!(
+            JobPanicked::from_payload(payload).message(),
+            "formatted boom"
+        );
+    }
+}
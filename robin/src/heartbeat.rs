@@ -0,0 +1,158 @@
+use job::JobId;
+use queue_adapters::{EnqueuedJob, QueueIdentifier};
+use std::env;
+use std::fs;
+use std::process;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// The heartbeat of a process running workers, as recorded in the queue backend.
+///
+/// The workers spawned by [`spawn_workers`](../worker/fn.spawn_workers.html) record a heartbeat
+/// every
+/// [`Config::heartbeat_interval`](../config/struct.Config.html#structfield.heartbeat_interval).
+/// List the processes that are alive with
+/// [`Connection::workers`](../connection/struct.Connection.html#method.workers).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct WorkerInfo {
+    id: String,
+    host: String,
+    pid: u32,
+    threads: usize,
+    queues: Vec<String>,
+    started_at: SystemTime,
+    heartbeat_at: SystemTime,
+    jobs: Vec<CurrentJob>,
+    version: String,
+}
+
+impl WorkerInfo {
+    /// Describe the current process, which runs `threads` workers performing jobs from `queues`.
+    pub fn new(threads: usize, queues: &[QueueIdentifier]) -> WorkerInfo {
+        let now = SystemTime::now();
+        WorkerInfo {
+            id: Uuid::new_v4().hyphenated().to_string(),
+            host: hostname(),
+            pid: process::id(),
+            threads,
+            queues: queues.iter().map(|iden| iden.name().to_string()).collect(),
+            started_at: now,
+            heartbeat_at: now,
+            jobs: vec![],
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Get the ID, which is unique to each call to `spawn_workers`
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the name of the host the process runs on
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Get the ID of the process
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Get the number of worker threads
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Get the names of the queues the workers perform jobs from
+    pub fn queues(&self) -> &[String] {
+        &self.queues
+    }
+
+    /// Get the time the workers were started
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    /// Get the time of the last heartbeat
+    pub fn heartbeat_at(&self) -> SystemTime {
+        self.heartbeat_at
+    }
+
+    /// Get the jobs being performed at the time of the last heartbeat
+    pub fn jobs(&self) -> &[CurrentJob] {
+        &self.jobs
+    }
+
+    /// Get the version of Robin the process runs
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Record a heartbeat happening now, while the given jobs are being performed.
+    pub fn beat(self, jobs: Vec<CurrentJob>) -> WorkerInfo {
+        WorkerInfo {
+            heartbeat_at: SystemTime::now(),
+            jobs,
+            ..self
+        }
+    }
+}
+
+/// A job a worker thread is performing.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CurrentJob {
+    thread: usize,
+    id: JobId,
+    name: String,
+    queue: String,
+    started_at: SystemTime,
+}
+
+impl CurrentJob {
+    /// Record that the worker thread with the given number started performing `enq_job`, which
+    /// was dequeued from `queue`.
+    pub fn new(thread: usize, queue: QueueIdentifier, enq_job: &EnqueuedJob) -> CurrentJob {
+        CurrentJob {
+            thread,
+            id: enq_job.id().clone(),
+            name: enq_job.name().to_string(),
+            queue: queue.name().to_string(),
+            started_at: SystemTime::now(),
+        }
+    }
+
+    /// Get the number of the worker thread, starting from 0
+    pub fn thread(&self) -> usize {
+        self.thread
+    }
+
+    /// Get the ID of the job
+    pub fn id(&self) -> &JobId {
+        &self.id
+    }
+
+    /// Get the name of the job
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the name of the queue the job came from
+    pub fn queue(&self) -> &str {
+        &self.queue
+    }
+
+    /// Get the time the worker started performing the job
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+}
+
+/// The name of the machine, from `$HOSTNAME` or `/etc/hostname`.
+fn hostname() -> String {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
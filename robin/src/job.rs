@@ -1,10 +1,14 @@
 extern crate serde_json;
 
+use config::RetryPolicy;
 use connection::Connection;
 use error::{Error, RobinResult};
 use queue_adapters::{JobQueue, QueueIdentifier, RetryCount};
 use serde::{Deserialize, Serialize};
 use std;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
 
 /// The result type returned when performing jobs
 pub type JobResult = Result<(), Box<std::error::Error>>;
@@ -51,6 +55,353 @@ pub trait Job<Q> {
 
     /// What the job actually does.
     fn perform(&self, args: &Args, con: &Connection<Q>) -> JobResult;
+
+    /// The options declared for the job in [`jobs!`](../macro.jobs.html).
+    fn options(&self) -> JobOptions {
+        JobOptions::default()
+    }
+}
+
+/// Implemented for a job with `A`, the type of its arguments, so methods that take any job can
+/// check the arguments they're given.
+///
+/// **NOTE:** You normally wouldn't need to implement this. The [`jobs!`](../macro.jobs.html) macro
+/// will implement it for you.
+pub trait TypedJob<A> {}
+
+/// The options a job can declare in [`jobs!`](../macro.jobs.html).
+///
+/// The methods are named after the keys used in `jobs!`, so
+/// `SendEmail(Args) => { queue: "mailers", retry: RetryPolicy::new(3) }` becomes
+/// `JobOptions::default().queue("mailers").retry(RetryPolicy::new(3))`.
+#[derive(Debug, Clone, Copy)]
+pub struct JobOptions {
+    /// The queue the job is put into. Defaults to the main queue.
+    pub queue: QueueIdentifier,
+
+    /// How the job is retried when it fails. Defaults to `None` which means
+    /// [`Config::retry_policy`](../config/struct.Config.html#method.retry_policy) is used.
+    pub retry: Option<RetryPolicy>,
+
+    /// Prevents identical jobs from being enqueued while one is already queued or running.
+    /// Defaults to `None` which means every job is enqueued.
+    pub unique: Option<UniqueJob>,
+
+    /// Limits how often the job is performed. Defaults to `None` which means no limit.
+    pub rate_limit: Option<RateLimit>,
+
+    /// Limits how many of the job are performed at once. Defaults to `None` which means no
+    /// limit.
+    pub concurrency: Option<ConcurrencyLimit>,
+
+    /// How long the job can take to perform before it's considered failed. Defaults to `None`
+    /// which means
+    /// [`Config::job_timeout`](../config/struct.Config.html#structfield.job_timeout) is used.
+    pub timeout: Option<Duration>,
+}
+
+impl JobOptions {
+    /// Put the job into the named queue.
+    pub fn queue(self, name: &'static str) -> JobOptions {
+        JobOptions {
+            queue: QueueIdentifier::Named(name),
+            ..self
+        }
+    }
+
+    /// Retry the job according to the given policy.
+    pub fn retry(self, policy: RetryPolicy) -> JobOptions {
+        JobOptions {
+            retry: Some(policy),
+            ..self
+        }
+    }
+
+    /// Make the job unique.
+    pub fn unique(self, unique: UniqueJob) -> JobOptions {
+        JobOptions {
+            unique: Some(unique),
+            ..self
+        }
+    }
+
+    /// Limit how often the job is performed.
+    pub fn rate_limit(self, rate_limit: RateLimit) -> JobOptions {
+        JobOptions {
+            rate_limit: Some(rate_limit),
+            ..self
+        }
+    }
+
+    /// Limit how many of the job are performed at once.
+    pub fn concurrency(self, concurrency: ConcurrencyLimit) -> JobOptions {
+        JobOptions {
+            concurrency: Some(concurrency),
+            ..self
+        }
+    }
+
+    /// Fail the job if it takes longer than `timeout` to perform.
+    pub fn timeout(self, timeout: Duration) -> JobOptions {
+        JobOptions {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+}
+
+impl Default for JobOptions {
+    fn default() -> JobOptions {
+        JobOptions {
+            queue: QueueIdentifier::Main,
+            retry: None,
+            unique: None,
+            rate_limit: None,
+            concurrency: None,
+            timeout: None,
+        }
+    }
+}
+
+/// Makes a job unique, so enqueuing it while an identical job is already queued or running
+/// returns the ID of that job instead of enqueuing a new one.
+///
+/// Jobs are identical if they have the same name and the same arguments, or the same custom key
+/// if one is given. The lock is released once the job succeeds, is moved to the dead queue, or
+/// is cancelled. Otherwise it expires after `ttl`, which should be longer than the job is
+/// expected to wait in the queue and run for.
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # use robin::prelude::*;
+/// use std::time::Duration;
+///
+/// jobs! {
+///     ExportReport(u32) => {
+///         unique: UniqueJob::new(Duration::from_secs(60 * 60)),
+///     },
+/// }
+/// #
+/// # impl ExportReport {
+/// #     fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy)]
+pub struct UniqueJob {
+    /// How long the lock is held if it isn't released.
+    pub ttl: Duration,
+
+    /// Computes the key identical jobs share from the arguments. Defaults to `None` which means
+    /// a hash of the serialized arguments is used.
+    pub key: Option<fn(&Args) -> String>,
+}
+
+impl UniqueJob {
+    /// Make a job unique by its arguments, holding the lock for at most `ttl`.
+    pub fn new(ttl: Duration) -> UniqueJob {
+        UniqueJob { ttl, key: None }
+    }
+
+    /// Use a custom function to compute the key identical jobs share.
+    pub fn key(self, key: fn(&Args) -> String) -> UniqueJob {
+        UniqueJob {
+            key: Some(key),
+            ..self
+        }
+    }
+
+    /// The key of the lock for a job with the given name and arguments.
+    pub fn lock_key(&self, name: &JobName, args: &Args) -> String {
+        match self.key {
+            Some(key) => format!("{}:{}", name.0, key(args)),
+            None => format!("{}:{:016x}", name.0, fnv1a(args.json().as_bytes())),
+        }
+    }
+}
+
+impl fmt::Debug for UniqueJob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "UniqueJob {{ ttl: {:?}, custom_key: {:?} }}",
+            self.ttl,
+            self.key.is_some()
+        )
+    }
+}
+
+/// Limits how many times a job is performed within a period, across all workers.
+///
+/// Workers check the limit right before performing a job. Jobs over the limit are scheduled to
+/// be performed once the limit allows it again. That doesn't count as a failure, so it doesn't
+/// use up a retry. If the limit can't be checked, for example because the queue backend is down,
+/// the job is scheduled for after
+/// [`Config::scheduled_poll_interval`](../config/struct.Config.html#structfield.scheduled_poll_interval)
+/// as if the limit was hit.
+///
+/// Jobs that call the same rate limited service can share a limit by giving it a name with
+/// [`shared`](#method.shared).
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # use robin::prelude::*;
+/// jobs! {
+///     SendSms(String) => {
+///         rate_limit: RateLimit::per_minute(100).shared("sms-provider"),
+///     },
+///     SendMms(String) => {
+///         rate_limit: RateLimit::per_minute(100).shared("sms-provider"),
+///     },
+/// }
+/// #
+/// # impl SendSms {
+/// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # impl SendMms {
+/// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RateLimit {
+    /// How many times the job can be performed within `period`.
+    pub limit: u32,
+
+    /// The length of the sliding window the limit applies to.
+    pub period: Duration,
+
+    /// The name of the limit, if it's shared between jobs. Defaults to `None` which means the
+    /// limit only applies to the job itself.
+    pub name: Option<&'static str>,
+}
+
+impl RateLimit {
+    /// Allow the job to be performed `limit` times within any window of length `period`.
+    pub fn new(limit: u32, period: Duration) -> RateLimit {
+        RateLimit {
+            limit,
+            period,
+            name: None,
+        }
+    }
+
+    /// Allow the job to be performed `limit` times per second.
+    pub fn per_second(limit: u32) -> RateLimit {
+        RateLimit::new(limit, Duration::from_secs(1))
+    }
+
+    /// Allow the job to be performed `limit` times per minute.
+    pub fn per_minute(limit: u32) -> RateLimit {
+        RateLimit::new(limit, Duration::from_secs(60))
+    }
+
+    /// Allow the job to be performed `limit` times per hour.
+    pub fn per_hour(limit: u32) -> RateLimit {
+        RateLimit::new(limit, Duration::from_secs(60 * 60))
+    }
+
+    /// Share the limit with all other jobs using a limit with the same name.
+    pub fn shared(self, name: &'static str) -> RateLimit {
+        RateLimit {
+            name: Some(name),
+            ..self
+        }
+    }
+
+    /// The key of the limit for the job with the given name.
+    pub fn key(&self, job_name: &JobName) -> String {
+        match self.name {
+            Some(name) => format!("shared:{}", name),
+            None => format!("job:{}", job_name.0),
+        }
+    }
+}
+
+/// Limits how many of a job are performed at once, across all workers in all processes.
+///
+/// A worker takes a slot before performing the job and gives it back afterwards. Jobs that can't
+/// get a slot are put back into their queue after
+/// [`Config::scheduled_poll_interval`](../config/struct.Config.html#structfield.scheduled_poll_interval),
+/// without using up a retry. A slot is taken for at most `lease`, so slots held by workers that
+/// crashed free up eventually. The lease should be longer than the job takes to perform. If the
+/// slots can't be checked, for example because the queue backend is down, the job is put back
+/// as if no slot was free.
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # use robin::prelude::*;
+/// jobs! {
+///     GenerateReport(u32) => {
+///         concurrency: ConcurrencyLimit::new(2),
+///     },
+/// }
+/// #
+/// # impl GenerateReport {
+/// #     fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ConcurrencyLimit {
+    /// How many of the job can be performed at once.
+    pub limit: u32,
+
+    /// How long a slot is held at most. Defaults to 30 minutes.
+    pub lease: Duration,
+
+    /// The name of the limit, if it's shared between jobs. Defaults to `None` which means the
+    /// limit only applies to the job itself.
+    pub name: Option<&'static str>,
+}
+
+impl ConcurrencyLimit {
+    /// Allow at most `limit` of the job to be performed at once.
+    pub fn new(limit: u32) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            limit,
+            lease: Duration::from_secs(30 * 60),
+            name: None,
+        }
+    }
+
+    /// Hold slots for at most `lease`.
+    pub fn lease(self, lease: Duration) -> ConcurrencyLimit {
+        ConcurrencyLimit { lease, ..self }
+    }
+
+    /// Share the limit with all other jobs using a limit with the same name.
+    pub fn shared(self, name: &'static str) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            name: Some(name),
+            ..self
+        }
+    }
+
+    /// The key of the limit for the job with the given name.
+    pub fn key(&self, job_name: &JobName) -> String {
+        match self.name {
+            Some(name) => format!("shared:{}", name),
+            None => format!("job:{}", job_name.0),
+        }
+    }
+}
+
+/// The 64 bit FNV-1a hash. Used rather than `DefaultHasher` since the hash has to be the same
+/// across processes and Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
 /// Trait for either performing immediately, or more commonly, later.
@@ -60,8 +411,23 @@ pub trait PerformJob<Q, A> {
     /// Perform the job right now without blocking.
     fn perform_now(&self, args: A, con: &Connection<Q>) -> RobinResult<()>;
 
-    /// Put the job into the queue for processing at a later point.
-    fn perform_later(&self, args: A, con: &Connection<Q>) -> RobinResult<()>;
+    /// Put the job into the queue for processing at a later point. Returns the ID of the
+    /// enqueued job.
+    fn perform_later(&self, args: A, con: &Connection<Q>) -> RobinResult<JobId>;
+
+    /// Put many jobs into the queue at once. Much faster than calling `perform_later` in a loop
+    /// since the jobs are sent to the queue together. Returns the IDs of the enqueued jobs in
+    /// the same order as the arguments.
+    fn perform_later_many<I>(&self, many_args: I, con: &Connection<Q>) -> RobinResult<Vec<JobId>>
+    where
+        I: IntoIterator<Item = A>;
+
+    /// Put the job into the queue for processing once `delay` has passed. Returns the ID of the
+    /// enqueued job.
+    fn perform_in(&self, args: A, delay: Duration, con: &Connection<Q>) -> RobinResult<JobId>;
+
+    /// Put the job into the queue for processing at `time`. Returns the ID of the enqueued job.
+    fn perform_at(&self, args: A, time: SystemTime, con: &Connection<Q>) -> RobinResult<JobId>;
 }
 
 impl<T, Q, A> PerformJob<Q, A> for T
@@ -75,21 +441,114 @@ where
             .map_err(|e| Error::JobFailed(e))
     }
 
-    fn perform_later(&self, args: A, con: &Connection<Q>) -> RobinResult<()> {
+    fn perform_later(&self, args: A, con: &Connection<Q>) -> RobinResult<JobId> {
+        let options = self.options();
         con.enqueue_to(
-            QueueIdentifier::Main,
+            options.queue,
             self.name(),
             &serialize_arg(args)?,
             RetryCount::NeverRetried,
+            options.unique,
+        )
+    }
+
+    fn perform_later_many<I>(&self, many_args: I, con: &Connection<Q>) -> RobinResult<Vec<JobId>>
+    where
+        I: IntoIterator<Item = A>,
+    {
+        let options = self.options();
+        let many_args = many_args
+            .into_iter()
+            .map(serialize_arg)
+            .collect::<RobinResult<Vec<_>>>()?;
+        con.enqueue_many_to(
+            options.queue,
+            self.name(),
+            &many_args,
+            RetryCount::NeverRetried,
+            options.unique,
+        )
+    }
+
+    fn perform_in(&self, args: A, delay: Duration, con: &Connection<Q>) -> RobinResult<JobId> {
+        self.perform_at(args, SystemTime::now() + delay, con)
+    }
+
+    fn perform_at(&self, args: A, time: SystemTime, con: &Connection<Q>) -> RobinResult<JobId> {
+        let options = self.options();
+        con.enqueue_at(
+            options.queue,
+            self.name(),
+            &serialize_arg(args)?,
+            RetryCount::NeverRetried,
+            time,
+            options.unique,
         )
     }
 }
 
-fn serialize_arg<T: Serialize>(value: T) -> RobinResult<Args> {
+#[doc(hidden)]
+pub fn serialize_arg<T: Serialize>(value: T) -> RobinResult<Args> {
     let json = serde_json::to_string(&value).map_err(Error::from)?;
     Ok(Args { json })
 }
 
+/// The unique ID given to a job when it's enqueued.
+///
+/// The ID stays the same when the job is retried or requeued from the dead queue.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct JobId(String);
+
+impl JobId {
+    /// Generate a new random ID.
+    pub fn new() -> JobId {
+        JobId(Uuid::new_v4().hyphenated().to_string())
+    }
+
+    /// Get the ID as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The ID of a job enqueued by an earlier version, which didn't give jobs IDs. It's derived
+    /// from the payload so the job keeps the same ID every time it's decoded.
+    #[doc(hidden)]
+    pub fn from_payload(payload: &str) -> JobId {
+        JobId(format!("legacy:{:016x}", fnv1a(payload.as_bytes())))
+    }
+
+    #[doc(hidden)]
+    pub fn missing() -> JobId {
+        JobId(String::new())
+    }
+
+    #[doc(hidden)]
+    pub fn is_missing(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Default for JobId {
+    fn default() -> JobId {
+        JobId::new()
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<T> From<T> for JobId
+where
+    T: Into<String>,
+{
+    fn from(t: T) -> JobId {
+        JobId(t.into())
+    }
+}
+
 /// A simple new type wrapper around strings.
 #[derive(Eq, PartialEq, Hash, Debug)]
 pub struct JobName(pub String);
@@ -102,3 +561,42 @@ where
         JobName(t.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_keys() {
+        let name = JobName::from("SendSms");
+        assert_eq!(RateLimit::per_minute(100).key(&name), "job:SendSms");
+        assert_eq!(
+            RateLimit::per_minute(100).shared("sms").key(&name),
+            "shared:sms"
+        );
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_unique_lock_key() {
+        let name = JobName::from("ExportReport");
+        let args = serialize_arg(1).unwrap();
+        let other_args = serialize_arg(2).unwrap();
+        let unique = UniqueJob::new(Duration::from_secs(1));
+
+        assert_eq!(unique.lock_key(&name, &args), unique.lock_key(&name, &args));
+        assert_ne!(unique.lock_key(&name, &args), unique.lock_key(&name, &other_args));
+
+        fn same_key(_: &Args) -> String {
+            "same".to_string()
+        }
+        let unique = unique.key(same_key);
+        assert_eq!(unique.lock_key(&name, &args), "ExportReport:same");
+        assert_eq!(unique.lock_key(&name, &other_args), "ExportReport:same");
+    }
+}
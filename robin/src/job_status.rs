@@ -0,0 +1,150 @@
+use std::time::SystemTime;
+
+/// Where a job is in its lifecycle.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum JobState {
+    /// The job is waiting in a queue.
+    Enqueued,
+
+    /// The job is waiting to be put into a queue at a later time.
+    Scheduled,
+
+    /// A worker is performing the job.
+    Running,
+
+    /// The job was performed successfully.
+    Succeeded,
+
+    /// The job failed and is waiting to be retried. Contains the number of times it has failed.
+    Retrying(u32),
+
+    /// The job failed too many times and was moved to the dead queue.
+    Dead,
+
+    /// The job was cancelled before it was performed.
+    Cancelled,
+}
+
+impl JobState {
+    /// `true` if the job hasn't been picked up by a worker yet, `false` otherwise.
+    pub fn is_waiting(&self) -> bool {
+        match *self {
+            JobState::Enqueued | JobState::Scheduled | JobState::Retrying(_) => true,
+            JobState::Running | JobState::Succeeded | JobState::Dead | JobState::Cancelled => {
+                false
+            }
+        }
+    }
+}
+
+/// One attempt at performing a job.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Attempt {
+    started_at: SystemTime,
+    finished_at: Option<SystemTime>,
+    error: Option<String>,
+}
+
+impl Attempt {
+    /// Get the time the worker started performing the job
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    /// Get the time the attempt finished, if it has
+    pub fn finished_at(&self) -> Option<SystemTime> {
+        self.finished_at
+    }
+
+    /// Get the error the attempt failed with, if it failed
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(|error| error.as_str())
+    }
+}
+
+/// The state of a job together with the history of attempts at performing it.
+///
+/// Only recorded if [`Config::job_status_ttl`](../config/struct.Config.html#structfield.job_status_ttl)
+/// is set. Get it with [`Connection::job_status`](../connection/struct.Connection.html#method.job_status).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct JobStatus {
+    state: JobState,
+    attempts: Vec<Attempt>,
+    updated_at: SystemTime,
+}
+
+impl JobStatus {
+    /// Create a new status without any attempts.
+    pub fn new(state: JobState) -> JobStatus {
+        JobStatus {
+            state,
+            attempts: vec![],
+            updated_at: SystemTime::now(),
+        }
+    }
+
+    /// Get the state
+    pub fn state(&self) -> JobState {
+        self.state
+    }
+
+    /// Get the attempts at performing the job, oldest first
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    /// Get the time of the last state change
+    pub fn updated_at(&self) -> SystemTime {
+        self.updated_at
+    }
+
+    /// Move the job to a new state.
+    pub fn transition(self, state: JobState) -> JobStatus {
+        JobStatus {
+            state,
+            updated_at: SystemTime::now(),
+            ..self
+        }
+    }
+
+    /// Record that a worker started performing the job.
+    pub fn start_attempt(mut self) -> JobStatus {
+        self.attempts.push(Attempt {
+            started_at: SystemTime::now(),
+            finished_at: None,
+            error: None,
+        });
+        self.transition(JobState::Running)
+    }
+
+    /// Record that the current attempt finished, and move the job to `state`.
+    pub fn finish_attempt(mut self, state: JobState, error: Option<&str>) -> JobStatus {
+        if let Some(attempt) = self.attempts.last_mut() {
+            if attempt.finished_at.is_none() {
+                attempt.finished_at = Some(SystemTime::now());
+                attempt.error = error.map(|error| error.to_string());
+            }
+        }
+        self.transition(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempt_history() {
+        let status = JobStatus::new(JobState::Enqueued)
+            .start_attempt()
+            .finish_attempt(JobState::Retrying(1), Some("boom"))
+            .start_attempt()
+            .finish_attempt(JobState::Succeeded, None);
+
+        assert_eq!(status.state(), JobState::Succeeded);
+        assert_eq!(status.attempts().len(), 2);
+        assert_eq!(status.attempts()[0].error(), Some("boom"));
+        assert_eq!(status.attempts()[1].error(), None);
+        assert!(status.attempts()[1].finished_at().is_some());
+    }
+}
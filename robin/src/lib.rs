@@ -100,36 +100,65 @@
 //! use robin::prelude::*;
 //! ```
 
+extern crate chrono;
+extern crate chrono_tz;
+extern crate cron;
+extern crate libc;
 #[macro_use]
 extern crate log;
 extern crate num_cpus;
+extern crate rand;
 extern crate redis;
-#[macro_use]
-extern crate robin_derives;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
+extern crate signal_hook;
 #[macro_use]
 extern crate typesafe_derive_builder;
+extern crate uuid;
 
 #[doc(hidden)]
 #[macro_use]
 #[cfg(not(release))]
 mod internal_macros;
 
+/// Contains the types for grouping jobs into batches.
+pub mod batch;
+
+/// Contains the types for chaining jobs.
+pub mod chain;
+
 /// Contains the connection type and functions for establishing connections.
 pub mod connection;
 
 /// Contains the error and result types used throughout Robin.
 pub mod error;
 
+/// Contains the types for the heartbeats recorded by running workers.
+pub mod heartbeat;
+
 /// Contains traits for enqueueing and performing jobs.
 ///
 /// **NOTE:** If you're using the [`jobs!`](../macro.jobs.html) macro you normally only need to know about the [`JobResult`](type.JobResult.html) type from this module. Everything else will be handled for you by [`jobs!`](../macro.jobs.html).
 pub mod job;
 
+/// Contains the types for tracking the status of jobs.
+pub mod job_status;
+
+/// Contains the types for reporting metrics about jobs and queues.
+pub mod metrics;
+
+/// Contains the types for wrapping enqueuing and performing jobs with middleware.
+pub mod middleware;
+
+/// Contains the types for enqueuing jobs periodically.
+pub mod periodic;
+
+/// Contains the types for listing the jobs in a queue.
+pub mod query;
+
 /// Contains functions for booting and running workers which perform jobs.
 pub mod worker;
 
@@ -141,18 +170,25 @@ pub mod macros;
 /// Contains the different types of queue backends supplied by Robin.
 pub mod queue_adapters;
 
-mod ticker;
-
 pub mod prelude {
     //! Reexports the most commonly used types and traits from the other modules.
     //! As long as you're doing standard things this is the only `use` you'll need.
 
-    pub use config::Config;
+    pub use batch::{Batch, BatchId, BatchStatus};
+    pub use chain::Chain;
+    pub use config::{Config, FetchStrategy, QueueSettings, RetryBackoff, RetryPolicy,
+                     UnknownJobHandler};
     pub use connection::{establish, Connection, LookupJob};
     pub use error::RobinResult;
-    pub use job::{Args, Job, JobName, JobResult, PerformJob};
+    pub use job::{Args, ConcurrencyLimit, Job, JobId, JobName, JobOptions, JobResult,
+                  PerformJob, RateLimit, UniqueJob};
+    pub use job_status::{JobState, JobStatus};
+    pub use metrics::{Metrics, MetricsSink};
+    pub use middleware::{ClientMiddleware, Middleware, ServerMiddleware};
+    pub use periodic::PeriodicJob;
+    pub use query::JobQuery;
     pub use queue_adapters::JobQueue;
-    pub use worker::{boot, spawn_workers};
+    pub use worker::{boot, spawn_workers, Shutdown};
 }
 
 /// Contains the types you'll need if you wish to use Redis as your backend.
@@ -5,7 +5,7 @@
 /// Generate the boilerplate for different types of jobs.
 ///
 /// Takes a comma separate list of struct names. Each struct will become a job that you can call
-/// `::perform_now` or `::perform_later` on. The type in the parenthesis is the argument type your job
+/// `::perform_now`, `::perform_later`, `::perform_later_many`, `::perform_in`, or `::perform_at` on. The type in the parenthesis is the argument type your job
 /// expects. Make sure that type implements `serde::Serialize` and `serde::Deserialize`.
 /// You also have to implement a static method named `perform` on each struct that does the actual
 /// work.
@@ -51,6 +51,66 @@
 /// # }
 /// ```
 ///
+/// ## Queues
+///
+/// Jobs are put into the main queue unless they name a queue of their own with `=>`. Named
+/// queues have to be listed in [`Config::queues`](config/struct.Config.html#structfield.queues)
+/// for the workers to perform their jobs.
+///
+/// ## Options
+///
+/// Instead of a queue name, `=>` can be followed by a block of options. The keys are the methods
+/// of [`JobOptions`](job/struct.JobOptions.html). Use `retry` to give the job its own
+/// [`RetryPolicy`](config/struct.RetryPolicy.html), for example to never retry validation
+/// errors:
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use robin::prelude::*;
+/// #
+/// # fn main() {
+/// jobs! {
+///     CreateUser(String) => {
+///         queue: "signups",
+///         retry: RetryPolicy::never(),
+///     },
+/// }
+/// # impl CreateUser {
+/// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # }
+/// ```
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use robin::prelude::*;
+/// #
+/// # fn main() {
+/// jobs! {
+///     ChargeCard(u32) => "payments",
+///     ExportReport(String) => "exports",
+///     SendPushNotification(String),
+/// }
+/// # impl ChargeCard {
+/// #     fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # impl ExportReport {
+/// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # impl SendPushNotification {
+/// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+/// # }
+/// ```
+///
 /// If you call `perform_later` or `perform_now` with an argument of the wrong type, you'll get a type error:
 ///
 /// ```compile_fail
@@ -157,6 +217,8 @@
 ///     }
 /// }
 ///
+/// impl robin::job::TypedJob<SendPushNotificationArgs> for SendPushNotification {}
+///
 /// impl SendPushNotification {
 ///     #[allow(dead_code)]
 ///     #[inline]
@@ -172,9 +234,66 @@
 ///     pub fn perform_later<Q: JobQueue>(
 ///         args: &SendPushNotificationArgs,
 ///         con: &Connection<Q>,
-///     ) -> RobinResult<()> {
+///     ) -> RobinResult<JobId> {
 ///         SendPushNotification.perform_later(args, con)
 ///     }
+///
+///     #[allow(dead_code)]
+///     #[inline]
+///     pub fn perform_later_many<Q: JobQueue>(
+///         many_args: &[SendPushNotificationArgs],
+///         con: &Connection<Q>,
+///     ) -> RobinResult<Vec<JobId>> {
+///         SendPushNotification.perform_later_many(many_args, con)
+///     }
+///
+///     #[allow(dead_code)]
+///     #[inline]
+///     pub fn periodic(
+///         expression: &str,
+///         args: &SendPushNotificationArgs,
+///     ) -> RobinResult<PeriodicJob> {
+///         PeriodicJob::new(
+///             JobName::from("SendPushNotification"),
+///             expression,
+///             &robin::job::serialize_arg(args)?,
+///         )
+///     }
+///
+///     #[allow(dead_code)]
+///     #[inline]
+///     pub fn chain<Q: JobQueue>(args: &SendPushNotificationArgs) -> Chain<Q> {
+///         Chain::new(&SendPushNotification, args)
+///     }
+///
+///     #[allow(dead_code)]
+///     #[inline]
+///     pub fn add_to_batch<Q: JobQueue>(
+///         args: &SendPushNotificationArgs,
+///         batch: &mut Batch<Q>,
+///     ) -> RobinResult<JobId> {
+///         batch.perform_later(&SendPushNotification, args)
+///     }
+///
+///     #[allow(dead_code)]
+///     #[inline]
+///     pub fn perform_in<Q: JobQueue>(
+///         args: &SendPushNotificationArgs,
+///         delay: ::std::time::Duration,
+///         con: &Connection<Q>,
+///     ) -> RobinResult<JobId> {
+///         SendPushNotification.perform_in(args, delay, con)
+///     }
+///
+///     #[allow(dead_code)]
+///     #[inline]
+///     pub fn perform_at<Q: JobQueue>(
+///         args: &SendPushNotificationArgs,
+///         time: ::std::time::SystemTime,
+///         con: &Connection<Q>,
+///     ) -> RobinResult<JobId> {
+///         SendPushNotification.perform_at(args, time, con)
+///     }
 /// }
 ///
 /// pub fn __robin_lookup_job<Q: JobQueue>(name: &JobName) -> Option<Box<Job<Q> + Send>> {
@@ -205,15 +324,7 @@
 #[macro_export]
 macro_rules! jobs {
     (
-        $id:ident($arg_type:ty),
-    ) => {
-        jobs! {
-            $id($arg_type)
-        }
-    };
-
-    (
-        $($id:ident($arg_type:ty)),*
+        $($id:ident($arg_type:ty) $(=> $options:tt)*),* $(,)*
     ) => {
         $(
             pub struct $id;
@@ -229,8 +340,17 @@ macro_rules! jobs {
                 fn perform(&self, args: &Args, con: &Connection<Q>) -> JobResult {
                     $id::perform(args.deserialize()?, con)
                 }
+
+                $(
+                    #[inline]
+                    fn options(&self) -> $crate::job::JobOptions {
+                        __robin_job_options!($options)
+                    }
+                )*
             }
 
+            impl $crate::job::TypedJob<$arg_type> for $id {}
+
             impl $id {
                 #[allow(dead_code)]
                 #[inline]
@@ -246,9 +366,66 @@ macro_rules! jobs {
                 pub fn perform_later<Q: JobQueue>(
                     args: &$arg_type,
                     con: &Connection<Q>,
-                ) -> RobinResult<()> {
+                ) -> RobinResult<JobId> {
                     $id.perform_later(args, con)
                 }
+
+                #[allow(dead_code)]
+                #[inline]
+                pub fn perform_later_many<Q: JobQueue>(
+                    many_args: &[$arg_type],
+                    con: &Connection<Q>,
+                ) -> RobinResult<Vec<JobId>> {
+                    $id.perform_later_many(many_args, con)
+                }
+
+                #[allow(dead_code)]
+                #[inline]
+                pub fn periodic(
+                    expression: &str,
+                    args: &$arg_type,
+                ) -> RobinResult<$crate::periodic::PeriodicJob> {
+                    $crate::periodic::PeriodicJob::new(
+                        JobName::from(stringify!($id)),
+                        expression,
+                        &$crate::job::serialize_arg(args)?,
+                    )
+                }
+
+                #[allow(dead_code)]
+                #[inline]
+                pub fn chain<Q: JobQueue>(args: &$arg_type) -> Chain<Q> {
+                    Chain::new(&$id, args)
+                }
+
+                #[allow(dead_code)]
+                #[inline]
+                pub fn add_to_batch<Q: JobQueue>(
+                    args: &$arg_type,
+                    batch: &mut Batch<Q>,
+                ) -> RobinResult<JobId> {
+                    batch.perform_later(&$id, args)
+                }
+
+                #[allow(dead_code)]
+                #[inline]
+                pub fn perform_in<Q: JobQueue>(
+                    args: &$arg_type,
+                    delay: ::std::time::Duration,
+                    con: &Connection<Q>,
+                ) -> RobinResult<JobId> {
+                    $id.perform_in(args, delay, con)
+                }
+
+                #[allow(dead_code)]
+                #[inline]
+                pub fn perform_at<Q: JobQueue>(
+                    args: &$arg_type,
+                    time: ::std::time::SystemTime,
+                    con: &Connection<Q>,
+                ) -> RobinResult<JobId> {
+                    $id.perform_at(args, time, con)
+                }
             }
         )*
 
@@ -264,6 +441,18 @@ macro_rules! jobs {
     };
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __robin_job_options {
+    ({ $($key:ident : $value:expr),* $(,)* }) => {
+        $crate::job::JobOptions::default()$(.$key($value))*
+    };
+
+    ($queue:expr) => {
+        $crate::job::JobOptions::default().queue($queue)
+    };
+}
+
 /// Creates a new connection used to enqueued jobs, using the given config.
 ///
 /// This macro requires that you're also using [`jobs!`](macro.jobs.html) to define your jobs.
@@ -370,10 +559,12 @@ macro_rules! robin_establish_connection {
 #[macro_export]
 macro_rules! robin_boot_worker {
     ($ty:ty, $config:expr, $queue_config:expr) => {
-        robin::worker::boot::<$ty, _, _>(
-            &$config.clone(),
-            $queue_config.clone(),
-            __robin_lookup_job,
+        ::std::process::exit(
+            robin::worker::boot::<$ty, _, _>(
+                &$config.clone(),
+                $queue_config.clone(),
+                __robin_lookup_job,
+            ).exit_code(),
         );
     };
 }
@@ -0,0 +1,458 @@
+use queue_adapters::QueueIdentifier;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How many seconds the metrics server waits for a client to send its request or read the
+/// response.
+const REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// The most bytes of request headers the metrics server reads, so a client can't make it buffer
+/// an endless request.
+const MAX_REQUEST_HEADERS_SIZE: u64 = 8 * 1024;
+
+/// Receives counts and timings from Robin, for example to send them to a monitoring system.
+///
+/// Every method has a default implementation that does nothing, so sinks only need to implement
+/// the ones they care about. Add sinks to
+/// [`Config::metrics`](../config/struct.Config.html#structfield.metrics). Robin comes with
+/// [`PrometheusSink`](struct.PrometheusSink.html).
+pub trait MetricsSink: Send + Sync {
+    /// A job was put into `queue`, or scheduled to be put into it later.
+    fn job_enqueued(&self, _name: &str, _queue: QueueIdentifier) {}
+
+    /// A worker performed a job, whether it succeeded or not. `duration` is how long performing
+    /// it took.
+    fn job_processed(&self, _name: &str, _duration: Duration) {}
+
+    /// Performing a job failed. Called after `job_processed`.
+    fn job_failed(&self, _name: &str) {}
+
+    /// A failed job was put back into its queue to be retried.
+    fn job_retried(&self, _name: &str) {}
+
+    /// A job was moved to the dead queue.
+    fn job_dead(&self, _name: &str) {}
+
+    /// The number of jobs in `queue`. Reported by the workers every
+    /// [`Config::scheduled_poll_interval`](../config/struct.Config.html#structfield.scheduled_poll_interval).
+    fn queue_depth(&self, _queue: QueueIdentifier, _size: usize) {}
+}
+
+/// The sinks that receive metrics.
+///
+/// Set it with [`Config::metrics`](../config/struct.Config.html#structfield.metrics).
+///
+/// ```rust
+/// # use robin::prelude::*;
+/// use robin::metrics::PrometheusSink;
+///
+/// # fn main() {
+/// let prometheus = PrometheusSink::new();
+/// // Serve the metrics on http://127.0.0.1:9898/metrics
+/// # if false {
+/// prometheus.serve("127.0.0.1:9898").unwrap();
+/// # }
+///
+/// let mut config = Config::default();
+/// config.metrics = Metrics::new().sink(prometheus);
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Metrics {
+    sinks: Vec<Arc<MetricsSink>>,
+}
+
+impl Metrics {
+    /// Create metrics without any sinks.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Add a sink that receives the metrics.
+    pub fn sink<S>(mut self, sink: S) -> Metrics
+    where
+        S: 'static + MetricsSink,
+    {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// `true` if there are no sinks.
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+}
+
+impl MetricsSink for Metrics {
+    fn job_enqueued(&self, name: &str, queue: QueueIdentifier) {
+        for sink in &self.sinks {
+            sink.job_enqueued(name, queue);
+        }
+    }
+
+    fn job_processed(&self, name: &str, duration: Duration) {
+        for sink in &self.sinks {
+            sink.job_processed(name, duration);
+        }
+    }
+
+    fn job_failed(&self, name: &str) {
+        for sink in &self.sinks {
+            sink.job_failed(name);
+        }
+    }
+
+    fn job_retried(&self, name: &str) {
+        for sink in &self.sinks {
+            sink.job_retried(name);
+        }
+    }
+
+    fn job_dead(&self, name: &str) {
+        for sink in &self.sinks {
+            sink.job_dead(name);
+        }
+    }
+
+    fn queue_depth(&self, queue: QueueIdentifier, size: usize) {
+        for sink in &self.sinks {
+            sink.queue_depth(queue, size);
+        }
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Metrics {{ sinks: {} }}", self.sinks.len())
+    }
+}
+
+/// The upper bounds, in seconds, of the buckets of the job duration histogram.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0
+];
+
+/// A sink that keeps the metrics in memory and renders them in the
+/// [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/).
+///
+/// Clones share the same metrics, so keep a clone around to render or serve them after adding
+/// the sink to the config.
+///
+/// The following metrics are recorded:
+///
+/// - `robin_jobs_enqueued_total`, labeled with `job` and `queue`
+/// - `robin_jobs_processed_total`, labeled with `job`
+/// - `robin_jobs_failed_total`, labeled with `job`
+/// - `robin_jobs_retried_total`, labeled with `job`
+/// - `robin_jobs_dead_total`, labeled with `job`
+/// - `robin_job_duration_seconds`, a histogram labeled with `job`
+/// - `robin_queue_depth`, labeled with `queue`
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusSink {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl PrometheusSink {
+    /// Create a sink with no metrics recorded.
+    pub fn new() -> PrometheusSink {
+        PrometheusSink::default()
+    }
+
+    /// Serve the metrics over HTTP on `addr`, at `/metrics`. Returns the address the server is
+    /// listening on, which is useful when binding to port 0.
+    ///
+    /// The server runs on a background thread until the process exits. Each request is handled
+    /// on its own thread, so a slow client doesn't hold up the others.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let sink = self.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        warn!("Failed to accept Prometheus metrics request\n{:?}", err);
+                        continue;
+                    }
+                };
+
+                let sink = sink.clone();
+                thread::spawn(move || {
+                    if let Err(err) = sink.respond(stream) {
+                        warn!("Failed to serve Prometheus metrics\n{:?}", err);
+                    }
+                });
+            }
+        });
+
+        info!("Serving Prometheus metrics on http://{}/metrics", local_addr);
+        Ok(local_addr)
+    }
+
+    /// Render the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().expect("metrics lock poisoned");
+        let mut out = String::new();
+
+        render_counter(
+            &mut out,
+            "robin_jobs_enqueued_total",
+            "Jobs enqueued.",
+            registry
+                .enqueued
+                .iter()
+                .map(|(&(ref job, ref queue), count)| {
+                    (format!("job=\"{}\",queue=\"{}\"", escape(job), escape(queue)), *count)
+                }),
+        );
+        render_counter(
+            &mut out,
+            "robin_jobs_processed_total",
+            "Jobs performed by the workers, whether they succeeded or not.",
+            job_labels(&registry.processed),
+        );
+        render_counter(
+            &mut out,
+            "robin_jobs_failed_total",
+            "Jobs that failed.",
+            job_labels(&registry.failed),
+        );
+        render_counter(
+            &mut out,
+            "robin_jobs_retried_total",
+            "Failed jobs put back into their queue to be retried.",
+            job_labels(&registry.retried),
+        );
+        render_counter(
+            &mut out,
+            "robin_jobs_dead_total",
+            "Jobs moved to the dead queue.",
+            job_labels(&registry.dead),
+        );
+
+        out.push_str("# HELP robin_job_duration_seconds How long performing jobs took.\n");
+        out.push_str("# TYPE robin_job_duration_seconds histogram\n");
+        for (job, histogram) in &registry.durations {
+            let job = escape(job);
+            let mut cumulative = 0;
+            for (bound, count) in DURATION_BUCKETS.iter().zip(&histogram.buckets) {
+                cumulative += count;
+                writeln!(
+                    out,
+                    "robin_job_duration_seconds_bucket{{job=\"{}\",le=\"{}\"}} {}",
+                    job, bound, cumulative
+                ).unwrap();
+            }
+            writeln!(
+                out,
+                "robin_job_duration_seconds_bucket{{job=\"{}\",le=\"+Inf\"}} {}",
+                job, histogram.count
+            ).unwrap();
+            writeln!(
+                out,
+                "robin_job_duration_seconds_sum{{job=\"{}\"}} {}",
+                job, histogram.sum
+            ).unwrap();
+            writeln!(
+                out,
+                "robin_job_duration_seconds_count{{job=\"{}\"}} {}",
+                job, histogram.count
+            ).unwrap();
+        }
+
+        out.push_str("# HELP robin_queue_depth Jobs waiting in the queue.\n");
+        out.push_str("# TYPE robin_queue_depth gauge\n");
+        for (queue, size) in &registry.queue_depths {
+            writeln!(out, "robin_queue_depth{{queue=\"{}\"}} {}", escape(queue), size).unwrap();
+        }
+
+        out
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        // The whole request is read before responding, since closing the connection with unread
+        // data makes some clients see a reset instead of the response
+        let mut request = BufReader::new((&stream).take(MAX_REQUEST_HEADERS_SIZE));
+        let mut request_line = String::new();
+        request.read_line(&mut request_line)?;
+        loop {
+            let mut header = String::new();
+            if request.read_line(&mut header)? == 0 || header == "\r\n" || header == "\n" {
+                break;
+            }
+        }
+
+        let path = request_line.split_whitespace().nth(1).unwrap_or("");
+        let (status, body) = if path == "/metrics" {
+            ("200 OK", self.render())
+        } else {
+            ("404 Not Found", String::new())
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Registry),
+    {
+        let mut registry = self.registry.lock().expect("metrics lock poisoned");
+        f(&mut registry)
+    }
+}
+
+impl MetricsSink for PrometheusSink {
+    fn job_enqueued(&self, name: &str, queue: QueueIdentifier) {
+        self.update(|registry| {
+            *registry
+                .enqueued
+                .entry((name.to_string(), queue.name().to_string()))
+                .or_insert(0) += 1
+        })
+    }
+
+    fn job_processed(&self, name: &str, duration: Duration) {
+        self.update(|registry| {
+            *registry.processed.entry(name.to_string()).or_insert(0) += 1;
+            registry
+                .durations
+                .entry(name.to_string())
+                .or_insert_with(Histogram::new)
+                .observe(duration);
+        })
+    }
+
+    fn job_failed(&self, name: &str) {
+        self.update(|registry| *registry.failed.entry(name.to_string()).or_insert(0) += 1)
+    }
+
+    fn job_retried(&self, name: &str) {
+        self.update(|registry| *registry.retried.entry(name.to_string()).or_insert(0) += 1)
+    }
+
+    fn job_dead(&self, name: &str) {
+        self.update(|registry| *registry.dead.entry(name.to_string()).or_insert(0) += 1)
+    }
+
+    fn queue_depth(&self, queue: QueueIdentifier, size: usize) {
+        self.update(|registry| {
+            registry
+                .queue_depths
+                .insert(queue.name().to_string(), size);
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    enqueued: BTreeMap<(String, String), u64>,
+    processed: BTreeMap<String, u64>,
+    failed: BTreeMap<String, u64>,
+    retried: BTreeMap<String, u64>,
+    dead: BTreeMap<String, u64>,
+    durations: BTreeMap<String, Histogram>,
+    queue_depths: BTreeMap<String, usize>,
+}
+
+#[derive(Debug)]
+struct Histogram {
+    /// The number of observations in each bucket of `DURATION_BUCKETS`, not counting the ones in
+    /// lower buckets.
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram {
+            buckets: vec![0; DURATION_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9;
+        if let Some(index) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[index] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+fn render_counter<I>(out: &mut String, name: &str, help: &str, samples: I)
+where
+    I: Iterator<Item = (String, u64)>,
+{
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} counter", name).unwrap();
+    for (labels, count) in samples {
+        writeln!(out, "{}{{{}}} {}", name, labels, count).unwrap();
+    }
+}
+
+fn job_labels<'a>(counts: &'a BTreeMap<String, u64>) -> Box<Iterator<Item = (String, u64)> + 'a> {
+    Box::new(
+        counts
+            .iter()
+            .map(|(job, count)| (format!("job=\"{}\"", escape(job)), *count)),
+    )
+}
+
+/// Escape a label value as required by the Prometheus text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rendering_prometheus_metrics() {
+        let sink = PrometheusSink::new();
+        sink.job_enqueued("SendEmail", QueueIdentifier::Named("mailers"));
+        sink.job_processed("SendEmail", Duration::from_millis(20));
+        sink.job_processed("SendEmail", Duration::from_secs(2));
+        sink.job_failed("SendEmail");
+        sink.queue_depth(QueueIdentifier::Main, 3);
+
+        let out = sink.render();
+        assert!(out.contains("robin_jobs_enqueued_total{job=\"SendEmail\",queue=\"mailers\"} 1\n"));
+        assert!(out.contains("robin_jobs_processed_total{job=\"SendEmail\"} 2\n"));
+        assert!(out.contains("robin_jobs_failed_total{job=\"SendEmail\"} 1\n"));
+        assert!(out.contains("robin_job_duration_seconds_bucket{job=\"SendEmail\",le=\"0.01\"} 0\n"));
+        assert!(out.contains("robin_job_duration_seconds_bucket{job=\"SendEmail\",le=\"0.025\"} 1\n"));
+        assert!(out.contains("robin_job_duration_seconds_bucket{job=\"SendEmail\",le=\"2.5\"} 2\n"));
+        assert!(out.contains("robin_job_duration_seconds_count{job=\"SendEmail\"} 2\n"));
+        assert!(out.contains("robin_queue_depth{queue=\"main\"} 3\n"));
+    }
+
+    #[test]
+    fn test_escaping_label_values() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,245 @@
+use error::RobinResult;
+use job::{Args, JobId, JobResult};
+use queue_adapters::EnqueuedJob;
+use std::fmt;
+use std::sync::Arc;
+
+/// Wraps enqueuing a job.
+///
+/// Client middleware is called when a job is enqueued with `perform_later`, `perform_later_many`,
+/// `perform_in`, `perform_at`, as part of a chain, or in a
+/// [`Batch`](../batch/struct.Batch.html).
+///
+/// Jobs enqueued together with `perform_later_many` or in a batch are passed through the
+/// middleware one at a time before any of them is put into the queue, and are then put into
+/// the queue all at once. For those jobs `next` returns before the job is in the queue, and
+/// errors from putting them there are returned to the caller without passing through the
+/// middleware.
+///
+/// Call `next` with the job to continue enqueuing it, or return without calling it to stop the
+/// job from being enqueued. Information added with
+/// [`EnqueuedJob::with_metadata`](../queue_adapters/struct.EnqueuedJob.html#method.with_metadata)
+/// is stored with the job and available to [`ServerMiddleware`](trait.ServerMiddleware.html).
+///
+/// ```rust
+/// # use robin::prelude::*;
+/// use robin::queue_adapters::EnqueuedJob;
+///
+/// struct TagTenant;
+///
+/// impl ClientMiddleware for TagTenant {
+///     fn call(
+///         &self,
+///         job: EnqueuedJob,
+///         _args: &Args,
+///         next: &mut FnMut(EnqueuedJob) -> RobinResult<JobId>,
+///     ) -> RobinResult<JobId> {
+///         next(job.with_metadata("tenant", "acme"))
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut config = Config::default();
+/// config.middleware = Middleware::new().client(TagTenant);
+/// # }
+/// ```
+pub trait ClientMiddleware: Send + Sync {
+    /// Enqueue `job` by calling `next`, doing whatever else is needed before or after.
+    fn call(
+        &self,
+        job: EnqueuedJob,
+        args: &Args,
+        next: &mut FnMut(EnqueuedJob) -> RobinResult<JobId>,
+    ) -> RobinResult<JobId>;
+}
+
+/// Wraps performing a job.
+///
+/// Server middleware is called by the workers each time they perform a job. Call `next` to
+/// perform the job, or return without calling it to skip performing it. Errors returned from the
+/// middleware are treated like errors returned from the job, so the job is retried.
+///
+/// ```rust
+/// # use robin::prelude::*;
+/// use robin::queue_adapters::EnqueuedJob;
+///
+/// struct LogTenant;
+///
+/// impl ServerMiddleware for LogTenant {
+///     fn call(
+///         &self,
+///         job: &EnqueuedJob,
+///         _args: &Args,
+///         next: &mut FnMut() -> JobResult,
+///     ) -> JobResult {
+///         println!("Performing {} for {:?}", job.name(), job.metadata("tenant"));
+///         next()
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut config = Config::default();
+/// config.middleware = Middleware::new().server(LogTenant);
+/// # }
+/// ```
+pub trait ServerMiddleware: Send + Sync {
+    /// Perform `job` by calling `next`, doing whatever else is needed before or after.
+    fn call(&self, job: &EnqueuedJob, args: &Args, next: &mut FnMut() -> JobResult) -> JobResult;
+}
+
+/// The middleware that wraps enqueuing and performing jobs.
+///
+/// Set it with [`Config::middleware`](../config/struct.Config.html#structfield.middleware).
+/// Middleware is called in the order it's added, so the first middleware added is the outermost.
+#[derive(Clone, Default)]
+pub struct Middleware {
+    client: Vec<Arc<ClientMiddleware>>,
+    server: Vec<Arc<ServerMiddleware>>,
+}
+
+impl Middleware {
+    /// Create a chain with no middleware.
+    pub fn new() -> Middleware {
+        Middleware::default()
+    }
+
+    /// Add middleware that wraps enqueuing jobs.
+    pub fn client<M>(mut self, middleware: M) -> Middleware
+    where
+        M: 'static + ClientMiddleware,
+    {
+        self.client.push(Arc::new(middleware));
+        self
+    }
+
+    /// Add middleware that wraps performing jobs.
+    pub fn server<M>(mut self, middleware: M) -> Middleware
+    where
+        M: 'static + ServerMiddleware,
+    {
+        self.server.push(Arc::new(middleware));
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn enqueue(
+        &self,
+        job: EnqueuedJob,
+        args: &Args,
+        enqueue: &mut FnMut(EnqueuedJob) -> RobinResult<JobId>,
+    ) -> RobinResult<JobId> {
+        call_client(&self.client, job, args, enqueue)
+    }
+
+    #[doc(hidden)]
+    pub fn perform(
+        &self,
+        job: &EnqueuedJob,
+        args: &Args,
+        perform: &mut FnMut() -> JobResult,
+    ) -> JobResult {
+        call_server(&self.server, job, args, perform)
+    }
+}
+
+impl fmt::Debug for Middleware {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Middleware {{ client: {}, server: {} }}",
+            self.client.len(),
+            self.server.len()
+        )
+    }
+}
+
+fn call_client(
+    chain: &[Arc<ClientMiddleware>],
+    job: EnqueuedJob,
+    args: &Args,
+    enqueue: &mut FnMut(EnqueuedJob) -> RobinResult<JobId>,
+) -> RobinResult<JobId> {
+    match chain.split_first() {
+        Some((middleware, rest)) => middleware.call(job, args, &mut |job: EnqueuedJob| {
+            call_client(rest, job, args, enqueue)
+        }),
+        None => enqueue(job),
+    }
+}
+
+fn call_server(
+    chain: &[Arc<ServerMiddleware>],
+    job: &EnqueuedJob,
+    args: &Args,
+    perform: &mut FnMut() -> JobResult,
+) -> JobResult {
+    match chain.split_first() {
+        Some((middleware, rest)) => {
+            middleware.call(job, args, &mut || call_server(rest, job, args, perform))
+        }
+        None => perform(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use job::serialize_arg;
+    use queue_adapters::RetryCount;
+    use std::sync::Mutex;
+
+    struct Record(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+    impl ServerMiddleware for Record {
+        fn call(
+            &self,
+            _job: &EnqueuedJob,
+            _args: &Args,
+            next: &mut FnMut() -> JobResult,
+        ) -> JobResult {
+            self.1.lock().unwrap().push(self.0);
+            next()
+        }
+    }
+
+    struct Halt;
+
+    impl ServerMiddleware for Halt {
+        fn call(
+            &self,
+            _job: &EnqueuedJob,
+            _args: &Args,
+            _next: &mut FnMut() -> JobResult,
+        ) -> JobResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_server_middleware_is_called_in_order() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let job = EnqueuedJob::new("Job", "{}", RetryCount::NeverRetried);
+        let args = serialize_arg(()).unwrap();
+
+        let middleware = Middleware::new()
+            .server(Record("first", Arc::clone(&calls)))
+            .server(Record("second", Arc::clone(&calls)));
+        middleware
+            .perform(&job, &args, &mut || {
+                calls.lock().unwrap().push("job");
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(*calls.lock().unwrap(), vec!["first", "second", "job"]);
+
+        let mut performed = false;
+        Middleware::new()
+            .server(Halt)
+            .perform(&job, &args, &mut || {
+                performed = true;
+                Ok(())
+            })
+            .unwrap();
+        assert!(!performed);
+    }
+}
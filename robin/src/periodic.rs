@@ -0,0 +1,235 @@
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use error::{Error, RobinResult};
+use job::{Args, JobName};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+pub use chrono_tz::Tz;
+
+/// The default for [`PeriodicJob::max_catch_up`](struct.PeriodicJob.html#method.max_catch_up).
+pub const DEFAULT_MAX_CATCH_UP: usize = 100;
+
+/// A job that's enqueued over and over according to a cron expression.
+///
+/// Add periodic jobs to
+/// [`Config::periodic_jobs`](../config/struct.Config.html#structfield.periodic_jobs) and the
+/// workers spawned by [`spawn_workers`](../worker/fn.spawn_workers.html) will enqueue them at the
+/// right times. Each run is claimed in the queue backend before it's enqueued, so when several
+/// processes run workers with the same config only one of them enqueues each run. If enqueuing
+/// fails the claim is given back, so the run is enqueued on a later tick.
+///
+/// The cron expression has a field for seconds, so `"0 30 9 * * Mon-Fri"` runs at 9:30 every
+/// weekday. Create periodic jobs with the `periodic` method generated by
+/// [`jobs!`](../macro.jobs.html).
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate robin;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # use robin::prelude::*;
+/// use robin::periodic::{CatchUp, Tz};
+///
+/// jobs! {
+///     SendInvoices(()),
+/// }
+/// #
+/// # impl SendInvoices {
+/// #     fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult { Ok(()) }
+/// # }
+///
+/// # fn main() {
+/// let mut config = Config::default();
+/// config.periodic_jobs.push(
+///     SendInvoices::periodic("0 0 6 1 * *", &())
+///         .unwrap()
+///         .time_zone(Tz::Europe__Copenhagen)
+///         .catch_up(CatchUp::All),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PeriodicJob {
+    name: String,
+    args: String,
+    expression: String,
+    schedule: Schedule,
+    time_zone: Tz,
+    catch_up: CatchUp,
+    max_catch_up: usize,
+}
+
+impl PeriodicJob {
+    /// Create a periodic job that performs the job named `name` with `args`. The job runs in UTC
+    /// and only the latest missed run is caught up on.
+    ///
+    /// Returns `Error::InvalidCronExpression` if the cron expression can't be parsed.
+    pub fn new(name: JobName, expression: &str, args: &Args) -> RobinResult<PeriodicJob> {
+        let schedule = Schedule::from_str(expression)
+            .map_err(|err| Error::InvalidCronExpression(format!("{}: {:?}", expression, err)))?;
+        let args = args.to_json()?;
+
+        Ok(PeriodicJob {
+            name: name.0,
+            args,
+            expression: expression.to_string(),
+            schedule,
+            time_zone: Tz::UTC,
+            catch_up: CatchUp::Latest,
+            max_catch_up: DEFAULT_MAX_CATCH_UP,
+        })
+    }
+
+    /// Interpret the cron expression in the given time zone.
+    pub fn time_zone(self, time_zone: Tz) -> PeriodicJob {
+        PeriodicJob { time_zone, ..self }
+    }
+
+    /// Set what happens to runs that were missed while no workers were running.
+    pub fn catch_up(self, catch_up: CatchUp) -> PeriodicJob {
+        PeriodicJob { catch_up, ..self }
+    }
+
+    /// Set the most runs enqueued at once with `CatchUp::All`. When more runs were missed only
+    /// the latest `max_catch_up` are enqueued, so a long outage doesn't flood the queue.
+    /// Defaults to [`DEFAULT_MAX_CATCH_UP`](constant.DEFAULT_MAX_CATCH_UP.html), and is at least
+    /// 1.
+    pub fn max_catch_up(self, max_catch_up: usize) -> PeriodicJob {
+        PeriodicJob {
+            max_catch_up: max_catch_up.max(1),
+            ..self
+        }
+    }
+
+    /// Get the key identifying the job. Processes that have the same periodic job in their
+    /// config share its runs.
+    pub fn key(&self) -> String {
+        format!(
+            "{}:{}:{:?}:{}",
+            self.name, self.expression, self.time_zone, self.args
+        )
+    }
+
+    /// Get the name of the job
+    pub fn name(&self) -> JobName {
+        JobName::from(self.name.as_str())
+    }
+
+    /// Get the serialized arguments of the job
+    pub fn args(&self) -> &str {
+        &self.args
+    }
+
+    /// The runs after `last_run` that are due at `now`, oldest first, according to the catch up
+    /// policy.
+    pub fn due_runs(&self, last_run: SystemTime, now: SystemTime) -> Vec<SystemTime> {
+        let last_run = DateTime::<Utc>::from(last_run).with_timezone(&self.time_zone);
+        let now = DateTime::<Utc>::from(now).with_timezone(&self.time_zone);
+
+        let keep = match self.catch_up {
+            CatchUp::All => self.max_catch_up,
+            CatchUp::Latest => 1,
+        };
+
+        // Only the latest runs are kept, so a long gap doesn't build up a huge list
+        let mut runs = VecDeque::with_capacity(keep);
+        for run in self.schedule.after(&last_run).take_while(|run| *run <= now) {
+            if runs.len() == keep {
+                runs.pop_front();
+            }
+            runs.push_back(SystemTime::from(run));
+        }
+        runs.into_iter().collect()
+    }
+}
+
+impl fmt::Debug for PeriodicJob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PeriodicJob")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .field("expression", &self.expression)
+            .field("time_zone", &self.time_zone)
+            .field("catch_up", &self.catch_up)
+            .field("max_catch_up", &self.max_catch_up)
+            .finish()
+    }
+}
+
+/// What happens to runs of a periodic job that were missed, for example while no workers were
+/// running.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CatchUp {
+    /// Enqueue the job once, no matter how many runs were missed.
+    Latest,
+
+    /// Enqueue the job once for every missed run, up to
+    /// [`PeriodicJob::max_catch_up`](struct.PeriodicJob.html#method.max_catch_up) runs.
+    All,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use job::serialize_arg;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn hourly(catch_up: CatchUp) -> PeriodicJob {
+        let args = serialize_arg(()).unwrap();
+        PeriodicJob::new(JobName::from("Report"), "0 0 * * * *", &args)
+            .unwrap()
+            .catch_up(catch_up)
+    }
+
+    #[test]
+    fn test_due_runs() {
+        let midnight = UNIX_EPOCH + Duration::from_secs(60 * 60 * 24 * 365);
+        let hour = Duration::from_secs(60 * 60);
+
+        assert!(hourly(CatchUp::All).due_runs(midnight, midnight).is_empty());
+        assert_eq!(
+            hourly(CatchUp::All).due_runs(midnight, midnight + hour),
+            vec![midnight + hour]
+        );
+        assert_eq!(
+            hourly(CatchUp::All).due_runs(midnight, midnight + hour * 3),
+            vec![midnight + hour, midnight + hour * 2, midnight + hour * 3]
+        );
+        assert_eq!(
+            hourly(CatchUp::Latest).due_runs(midnight, midnight + hour * 3),
+            vec![midnight + hour * 3]
+        );
+    }
+
+    #[test]
+    fn test_due_runs_after_a_long_gap() {
+        let midnight = UNIX_EPOCH + Duration::from_secs(60 * 60 * 24 * 365);
+        let hour = Duration::from_secs(60 * 60);
+        let year_later = midnight + hour * 24 * 365;
+
+        let runs = hourly(CatchUp::All).due_runs(midnight, year_later);
+        assert_eq!(runs.len(), DEFAULT_MAX_CATCH_UP);
+        assert_eq!(runs[0], year_later - hour * (DEFAULT_MAX_CATCH_UP as u32 - 1));
+        assert_eq!(runs[DEFAULT_MAX_CATCH_UP - 1], year_later);
+
+        assert_eq!(
+            hourly(CatchUp::All)
+                .max_catch_up(2)
+                .due_runs(midnight, year_later),
+            vec![year_later - hour, year_later]
+        );
+        assert_eq!(
+            hourly(CatchUp::Latest).due_runs(midnight, year_later),
+            vec![year_later]
+        );
+    }
+
+    #[test]
+    fn test_invalid_expression() {
+        let args = serialize_arg(()).unwrap();
+        assert!(PeriodicJob::new(JobName::from("Report"), "every hour", &args).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use queue_adapters::EnqueuedJob;
+use serde_json::Value;
+
+/// Which jobs to list with
+/// [`Connection::list_jobs`](../connection/struct.Connection.html#method.list_jobs).
+///
+/// By default the first 100 jobs are listed, whatever their name or arguments.
+///
+/// ```rust
+/// # use robin::prelude::*;
+/// use robin::query::JobQuery;
+///
+/// # fn main() {
+/// // The second page of "SendEmail" jobs sent to user 42
+/// let query = JobQuery::new()
+///     .name("SendEmail")
+///     .args_match("/user_id", 42)
+///     .offset(20)
+///     .limit(20);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct JobQuery {
+    name: Option<String>,
+    args: Vec<(String, Value)>,
+
+    /// The number of matching jobs to skip.
+    /// Defaults to 0.
+    pub offset: usize,
+
+    /// The maximum number of jobs to list.
+    /// Defaults to 100.
+    pub limit: usize,
+}
+
+impl JobQuery {
+    /// Create a query that matches all jobs.
+    pub fn new() -> JobQuery {
+        JobQuery::default()
+    }
+
+    /// Only match jobs with the given name.
+    pub fn name(self, name: &str) -> JobQuery {
+        JobQuery {
+            name: Some(name.to_string()),
+            ..self
+        }
+    }
+
+    /// Only match jobs whose arguments contain `value` at `pointer`. The pointer is a
+    /// [JSON pointer](https://tools.ietf.org/html/rfc6901) into the arguments, such as
+    /// `"/user/id"` or `"/0"` for the first element of a tuple. Use `""` to match the whole
+    /// arguments.
+    ///
+    /// Calling this again adds another match, and jobs have to match all of them.
+    pub fn args_match<V: Into<Value>>(mut self, pointer: &str, value: V) -> JobQuery {
+        self.args.push((pointer.to_string(), value.into()));
+        self
+    }
+
+    /// Set the number of matching jobs to skip.
+    pub fn offset(self, offset: usize) -> JobQuery {
+        JobQuery { offset, ..self }
+    }
+
+    /// Set the maximum number of jobs to list.
+    pub fn limit(self, limit: usize) -> JobQuery {
+        JobQuery { limit, ..self }
+    }
+
+    /// `true` if the query matches all jobs, so it only paginates.
+    pub fn matches_all(&self) -> bool {
+        self.name.is_none() && self.args.is_empty()
+    }
+
+    /// `true` if the job matches the name and arguments of the query. Jobs with arguments that
+    /// can't be decoded don't match if the query matches on arguments.
+    pub fn matches(&self, enq_job: &EnqueuedJob) -> bool {
+        if let Some(ref name) = self.name {
+            if enq_job.name() != name {
+                return false;
+            }
+        }
+
+        if self.args.is_empty() {
+            return true;
+        }
+
+        let args = match enq_job.decoded_args() {
+            Ok(args) => args,
+            Err(_) => return false,
+        };
+        self.args
+            .iter()
+            .all(|&(ref pointer, ref value)| args.pointer(pointer) == Some(value))
+    }
+}
+
+impl Default for JobQuery {
+    fn default() -> JobQuery {
+        JobQuery {
+            name: None,
+            args: vec![],
+            offset: 0,
+            limit: 100,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use job::serialize_arg;
+    use queue_adapters::RetryCount;
+
+    #[derive(Serialize)]
+    struct Email {
+        user_id: u32,
+        subject: &'static str,
+    }
+
+    fn send_email(user_id: u32) -> EnqueuedJob {
+        let args = serialize_arg(Email {
+            user_id,
+            subject: "Hi",
+        }).unwrap();
+        EnqueuedJob::new("SendEmail", &args.to_json().unwrap(), RetryCount::NeverRetried)
+    }
+
+    #[test]
+    fn test_matching_jobs() {
+        assert!(JobQuery::new().matches(&send_email(1)));
+        assert!(JobQuery::new().name("SendEmail").matches(&send_email(1)));
+        assert!(!JobQuery::new().name("SendSms").matches(&send_email(1)));
+
+        let query = JobQuery::new()
+            .args_match("/user_id", 1)
+            .args_match("/subject", "Hi");
+        assert!(query.matches(&send_email(1)));
+        assert!(!query.matches(&send_email(2)));
+        assert!(!JobQuery::new().args_match("/missing", 1).matches(&send_email(1)));
+    }
+}
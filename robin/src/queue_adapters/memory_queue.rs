@@ -1,11 +1,9 @@
-#![allow(unused_imports)]
 use super::*;
-use error::*;
+use batch::{BatchCallback, BatchId, BatchStatus};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::default::Default;
-use std::{sync::{mpsc::{channel, Receiver, SendError, Sender},
-                 Arc,
-                 Mutex},
-          time::Duration};
+use std::{sync::{Arc, Condvar, Mutex},
+          time::{Duration, Instant, SystemTime}};
 
 /// A queue backend the stores the jobs in-memory. Normally only used during testing.
 #[allow(missing_debug_implementations)]
@@ -14,113 +12,190 @@ pub struct MemoryQueue {
 }
 
 /// The type used to configure an in-memory queue.
-#[derive(Debug)]
+///
+/// Queues created from clones of the same config share their jobs.
+#[derive(Debug, Clone)]
 pub struct MemoryQueueConfig {
     timeout: Duration,
-    send: Arc<Mutex<Sender<EnqueuedJob>>>,
-    recv: Arc<Mutex<Receiver<EnqueuedJob>>>,
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    queues: Mutex<Queues>,
+    job_enqueued: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Queues {
+    jobs: HashMap<QueueIdentifier, VecDeque<EnqueuedJob>>,
+    scheduled: HashMap<QueueIdentifier, Vec<(SystemTime, EnqueuedJob)>>,
+    poisoned: Vec<PoisonedJob>,
+    statuses: HashMap<JobId, (JobStatus, Instant)>,
+    unique_locks: HashMap<String, (JobId, Instant)>,
+    batches: HashMap<BatchId, StoredBatch>,
+    periodic_runs: HashMap<String, SystemTime>,
+    rate_limits: HashMap<String, VecDeque<Instant>>,
+    concurrency_slots: HashMap<String, HashMap<JobId, Instant>>,
+    workers: HashMap<String, (WorkerInfo, Instant)>,
+}
+
+#[derive(Debug)]
+struct StoredBatch {
+    status: BatchStatus,
+    pending: HashSet<JobId>,
+    callbacks: Vec<BatchCallback>,
+    expires_at: Option<Instant>,
+}
+
+impl Queues {
+    fn batch(&mut self, id: &BatchId) -> Option<&mut StoredBatch> {
+        let now = Instant::now();
+        self.batches.retain(|_, batch| batch.expires_at.map_or(true, |at| at > now));
+        self.batches.get_mut(id)
+    }
 }
 
 impl MemoryQueueConfig {
     /// Create a new `MemoryQueueConfig`
     pub fn new(timeout: Duration) -> MemoryQueueConfig {
-        let (send, recv) = channel();
-
         MemoryQueueConfig {
             timeout,
-            send: Arc::new(Mutex::new(send)),
-            recv: Arc::new(Mutex::new(recv)),
+            shared: Arc::new(Shared::default()),
         }
     }
 }
 
-impl MemoryQueueConfig {
-    fn enqueue(&self, enq_job: EnqueuedJob) -> JobQueueResult<()> {
-        self.send
+impl Default for MemoryQueueConfig {
+    fn default() -> MemoryQueueConfig {
+        MemoryQueueConfig::new(Duration::from_millis(100))
+    }
+}
+
+impl MemoryQueue {
+    fn with_queues<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Queues) -> T,
+    {
+        let mut queues = self.config
+            .shared
+            .queues
             .lock()
-            .expect("mutex was poisoned")
-            .send(enq_job)
-            .map_err(|e| (e, ErrorOrigin::Enqueue))?;
+            .expect("mutex was poisoned");
+        f(&mut queues)
+    }
+}
+
+impl JobQueue for MemoryQueue {
+    type Config = MemoryQueueConfig;
+
+    fn new(config: &MemoryQueueConfig) -> JobQueueResult<Self> {
+        Ok(MemoryQueue {
+            config: config.clone(),
+        })
+    }
+
+    fn enqueue(&self, iden: QueueIdentifier, enq_job: EnqueuedJob) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            queues
+                .jobs
+                .entry(iden)
+                .or_insert_with(VecDeque::new)
+                .push_back(enq_job)
+        });
+        self.config.shared.job_enqueued.notify_all();
         Ok(())
     }
 
-    fn dequeue(&self) -> Result<EnqueuedJob, NoJobDequeued> {
-        self.recv
-            .lock()
-            .expect("mutex was poisoned")
-            .recv_timeout(self.timeout)
-            .map_err(|_| NoJobDequeued::BecauseTimeout)
+    fn enqueue_many(&self, iden: QueueIdentifier, enq_jobs: Vec<EnqueuedJob>) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            queues
+                .jobs
+                .entry(iden)
+                .or_insert_with(VecDeque::new)
+                .extend(enq_jobs)
+        });
+        self.config.shared.job_enqueued.notify_all();
+        Ok(())
     }
 
-    fn delete_all(&self) -> JobQueueResult<()> {
-        let recv = self.recv.lock().expect("mutex was poisoned");
-        loop {
-            if let Err(_) = recv.try_recv() {
-                break;
-            }
-        }
+    fn put_back(&self, iden: QueueIdentifier, enq_job: EnqueuedJob) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            queues
+                .jobs
+                .entry(iden)
+                .or_insert_with(VecDeque::new)
+                .push_front(enq_job)
+        });
+        self.config.shared.job_enqueued.notify_all();
         Ok(())
     }
 
-    fn size(&self) -> JobQueueResult<usize> {
-        let mut jobs = vec![];
-        let mut count = 0;
+    fn dequeue(
+        &self,
+        idens: &[QueueIdentifier],
+    ) -> Result<(QueueIdentifier, EnqueuedJob), NoJobDequeued> {
+        let deadline = Instant::now() + self.config.timeout;
+        let mut queues = self.config
+            .shared
+            .queues
+            .lock()
+            .expect("mutex was poisoned");
 
-        let recv = self.recv.lock().expect("mutex was poisoned");
         loop {
-            if let Ok(job) = recv.try_recv() {
-                count += 1;
-                jobs.push(job);
-            } else {
-                break;
+            for &iden in idens {
+                if let Some(enq_job) = queues.jobs.get_mut(&iden).and_then(|jobs| jobs.pop_front())
+                {
+                    return Ok((iden, enq_job));
+                }
             }
-        }
-
-        let send = self.send.lock().expect("mutex was poisoned");
-        for job in jobs {
-            send.send(job);
-        }
-
-        Ok(count)
-    }
-}
 
-impl Default for MemoryQueueConfig {
-    fn default() -> MemoryQueueConfig {
-        MemoryQueueConfig::new(Duration::from_millis(100))
-    }
-}
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(NoJobDequeued::BecauseTimeout);
+            }
 
-impl Clone for MemoryQueueConfig {
-    fn clone(&self) -> MemoryQueueConfig {
-        MemoryQueueConfig {
-            timeout: self.timeout.clone(),
-            send: Arc::clone(&self.send),
-            recv: Arc::clone(&self.recv),
+            queues = self.config
+                .shared
+                .job_enqueued
+                .wait_timeout(queues, deadline - now)
+                .expect("mutex was poisoned")
+                .0;
         }
     }
-}
 
-impl JobQueue for MemoryQueue {
-    type Config = MemoryQueueConfig;
-
-    fn new(config: &MemoryQueueConfig) -> JobQueueResult<(Self, Self)> {
-        Ok((
-            MemoryQueue {
-                config: config.clone(),
-            },
-            MemoryQueue {
-                config: config.clone(),
-            },
-        ))
+    fn enqueue_at(
+        &self,
+        iden: QueueIdentifier,
+        enq_job: EnqueuedJob,
+        time: SystemTime,
+    ) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            queues
+                .scheduled
+                .entry(iden)
+                .or_insert_with(Vec::new)
+                .push((time, enq_job))
+        });
+        Ok(())
     }
 
-    fn enqueue(&self, enq_job: EnqueuedJob) -> JobQueueResult<()> {
-        self.config.enqueue(enq_job)
-    }
+    fn enqueue_due_jobs(&self, iden: QueueIdentifier, now: SystemTime) -> JobQueueResult<usize> {
+        let mut due = self.with_queues(|queues| {
+            let scheduled = queues.scheduled.entry(iden).or_insert_with(Vec::new);
+            let (due, not_due): (Vec<_>, Vec<_>) =
+                scheduled.drain(..).partition(|&(time, _)| time <= now);
+            *scheduled = not_due;
+            due
+        });
 
-    fn dequeue(&self) -> Result<EnqueuedJob, NoJobDequeued> {
-        self.config.dequeue()
+        due.sort_by_key(|&(time, _)| time);
+        let count = due.len();
+        for (_, enq_job) in due {
+            self.enqueue(iden, enq_job)?;
+        }
+
+        Ok(count)
     }
 
     /// Delete all jobs from the queue.
@@ -129,7 +204,7 @@ impl JobQueue for MemoryQueue {
     /// # extern crate robin;
     /// # use robin::prelude::*;
     /// use robin::memory_queue::*;
-    /// use robin::queue_adapters::{JobQueueResult, EnqueuedJob, RetryCount};
+    /// use robin::queue_adapters::{JobQueueResult, EnqueuedJob, QueueIdentifier, RetryCount};
     ///
     /// # use std::error::Error;
     /// # fn main() {
@@ -138,21 +213,25 @@ impl JobQueue for MemoryQueue {
     /// # fn try_main() -> JobQueueResult<()> {
     /// #
     /// let config = MemoryQueueConfig::default();
-    /// let (q, _retry_q) = MemoryQueue::new(&config)?;
+    /// let q = MemoryQueue::new(&config)?;
     ///
     /// let job = EnqueuedJob::new("name", "args", RetryCount::NeverRetried);
-    /// q.enqueue(job);
+    /// q.enqueue(QueueIdentifier::Main, job);
     ///
-    /// assert_eq!(q.size()?, 1);
+    /// assert_eq!(q.size(QueueIdentifier::Main)?, 1);
     ///
-    /// q.delete_all();
+    /// q.delete_all(QueueIdentifier::Main);
     ///
-    /// assert_eq!(q.size()?, 0);
+    /// assert_eq!(q.size(QueueIdentifier::Main)?, 0);
     /// # Ok(())
     /// # }
     /// ```
-    fn delete_all(&self) -> JobQueueResult<()> {
-        self.config.delete_all()
+    fn delete_all(&self, iden: QueueIdentifier) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            queues.jobs.remove(&iden);
+            queues.scheduled.remove(&iden);
+        });
+        Ok(())
     }
 
     /// Get the number of jobs in the queue.
@@ -161,7 +240,7 @@ impl JobQueue for MemoryQueue {
     /// # extern crate robin;
     /// # use robin::prelude::*;
     /// use robin::memory_queue::*;
-    /// use robin::queue_adapters::{JobQueueResult, EnqueuedJob, RetryCount};
+    /// use robin::queue_adapters::{JobQueueResult, EnqueuedJob, QueueIdentifier, RetryCount};
     ///
     /// # use std::error::Error;
     /// # fn main() {
@@ -170,19 +249,402 @@ impl JobQueue for MemoryQueue {
     /// # fn try_main() -> JobQueueResult<()> {
     /// #
     /// let config = MemoryQueueConfig::default();
-    /// let (q, _retry_q) = MemoryQueue::new(&config)?;
+    /// let q = MemoryQueue::new(&config)?;
     ///
-    /// assert_eq!(q.size()?, 0);
+    /// assert_eq!(q.size(QueueIdentifier::Main)?, 0);
     ///
     /// let job = EnqueuedJob::new("name", "args", RetryCount::NeverRetried);
-    /// q.enqueue(job);
+    /// q.enqueue(QueueIdentifier::Main, job);
     ///
-    /// assert_eq!(q.size()?, 1);
+    /// assert_eq!(q.size(QueueIdentifier::Main)?, 1);
+    /// assert_eq!(q.size(QueueIdentifier::Retry)?, 0);
     /// # Ok(())
     /// # }
     /// ```
-    fn size(&self) -> JobQueueResult<usize> {
-        self.config.size()
+    fn size(&self, iden: QueueIdentifier) -> JobQueueResult<usize> {
+        Ok(self.with_queues(|queues| queues.jobs.get(&iden).map_or(0, |jobs| jobs.len())))
+    }
+
+    /// Get the number of jobs waiting to be put into the queue.
+    ///
+    /// ```
+    /// # extern crate robin;
+    /// # use robin::prelude::*;
+    /// use robin::memory_queue::*;
+    /// use robin::queue_adapters::{JobQueueResult, EnqueuedJob, QueueIdentifier, RetryCount};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// # fn main() {
+    /// # try_main().unwrap();
+    /// # }
+    /// # fn try_main() -> JobQueueResult<()> {
+    /// #
+    /// let config = MemoryQueueConfig::default();
+    /// let q = MemoryQueue::new(&config)?;
+    ///
+    /// let in_an_hour = SystemTime::now() + Duration::from_secs(60 * 60);
+    /// let job = EnqueuedJob::new("name", "args", RetryCount::NeverRetried);
+    /// q.enqueue_at(QueueIdentifier::Main, job, in_an_hour);
+    ///
+    /// assert_eq!(q.scheduled_size(QueueIdentifier::Main)?, 1);
+    /// assert_eq!(q.size(QueueIdentifier::Main)?, 0);
+    ///
+    /// q.enqueue_due_jobs(QueueIdentifier::Main, in_an_hour)?;
+    ///
+    /// assert_eq!(q.scheduled_size(QueueIdentifier::Main)?, 0);
+    /// assert_eq!(q.size(QueueIdentifier::Main)?, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn scheduled_size(&self, iden: QueueIdentifier) -> JobQueueResult<usize> {
+        Ok(self.with_queues(|queues| {
+            queues.scheduled.get(&iden).map_or(0, |jobs| jobs.len())
+        }))
+    }
+
+    fn jobs(&self, iden: QueueIdentifier) -> JobQueueResult<Vec<EnqueuedJob>> {
+        Ok(self.with_queues(|queues| {
+            queues
+                .jobs
+                .get(&iden)
+                .map_or_else(Vec::new, |jobs| jobs.iter().cloned().collect())
+        }))
+    }
+
+    fn jobs_range(
+        &self,
+        iden: QueueIdentifier,
+        offset: usize,
+        limit: usize,
+    ) -> JobQueueResult<Vec<EnqueuedJob>> {
+        Ok(self.with_queues(|queues| {
+            queues.jobs.get(&iden).map_or_else(Vec::new, |jobs| {
+                jobs.iter().skip(offset).take(limit).cloned().collect()
+            })
+        }))
+    }
+
+    fn remove(&self, iden: QueueIdentifier, enq_job: &EnqueuedJob) -> JobQueueResult<bool> {
+        Ok(self.with_queues(|queues| {
+            let jobs = match queues.jobs.get_mut(&iden) {
+                Some(jobs) => jobs,
+                None => return false,
+            };
+
+            match jobs.iter().position(|job| job == enq_job) {
+                Some(idx) => {
+                    jobs.remove(idx);
+                    true
+                }
+                None => false,
+            }
+        }))
+    }
+
+    fn move_job(
+        &self,
+        from: QueueIdentifier,
+        enq_job: &EnqueuedJob,
+        to: QueueIdentifier,
+        moved_job: EnqueuedJob,
+    ) -> JobQueueResult<bool> {
+        let moved = self.with_queues(|queues| {
+            let removed = match queues.jobs.get_mut(&from) {
+                Some(jobs) => match jobs.iter().position(|job| job == enq_job) {
+                    Some(idx) => jobs.remove(idx).is_some(),
+                    None => false,
+                },
+                None => false,
+            };
+
+            if removed {
+                queues
+                    .jobs
+                    .entry(to)
+                    .or_insert_with(VecDeque::new)
+                    .push_back(moved_job);
+            }
+            removed
+        });
+
+        if moved {
+            self.config.shared.job_enqueued.notify_all();
+        }
+        Ok(moved)
+    }
+
+    fn poison(&self, poisoned_job: PoisonedJob) -> JobQueueResult<()> {
+        self.with_queues(|queues| queues.poisoned.push(poisoned_job));
+        Ok(())
+    }
+
+    fn poisoned_jobs(&self) -> JobQueueResult<Vec<PoisonedJob>> {
+        Ok(self.with_queues(|queues| queues.poisoned.clone()))
+    }
+
+    fn delete_all_poisoned(&self) -> JobQueueResult<()> {
+        self.with_queues(|queues| queues.poisoned.clear());
+        Ok(())
+    }
+
+    fn set_job_status(
+        &self,
+        id: &JobId,
+        status: &JobStatus,
+        ttl: Duration,
+    ) -> JobQueueResult<()> {
+        let expires_at = Instant::now() + ttl;
+        self.with_queues(|queues| {
+            queues
+                .statuses
+                .insert(id.clone(), (status.clone(), expires_at))
+        });
+        Ok(())
+    }
+
+    fn lock_unique(&self, key: &str, id: &JobId, ttl: Duration) -> JobQueueResult<Option<JobId>> {
+        Ok(self.with_queues(|queues| {
+            let now = Instant::now();
+            queues
+                .unique_locks
+                .retain(|_, &mut (_, expires_at)| expires_at > now);
+
+            if let Some(&(ref holder, _)) = queues.unique_locks.get(key) {
+                return Some(holder.clone());
+            }
+
+            queues
+                .unique_locks
+                .insert(key.to_string(), (id.clone(), now + ttl));
+            None
+        }))
+    }
+
+    fn unlock_unique(&self, key: &str, id: &JobId) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            let held_by_job = queues
+                .unique_locks
+                .get(key)
+                .map_or(false, |&(ref holder, _)| holder == id);
+
+            if held_by_job {
+                queues.unique_locks.remove(key);
+            }
+        });
+        Ok(())
+    }
+
+    fn job_status(&self, id: &JobId) -> JobQueueResult<Option<JobStatus>> {
+        Ok(self.with_queues(|queues| {
+            let now = Instant::now();
+            queues.statuses.retain(|_, &mut (_, expires_at)| expires_at > now);
+            queues.statuses.get(id).map(|&(ref status, _)| status.clone())
+        }))
+    }
+
+    fn swap_job_status(
+        &self,
+        id: &JobId,
+        previous: Option<&JobStatus>,
+        status: &JobStatus,
+        ttl: Duration,
+    ) -> JobQueueResult<bool> {
+        Ok(self.with_queues(|queues| {
+            let now = Instant::now();
+            queues.statuses.retain(|_, &mut (_, expires_at)| expires_at > now);
+            if queues.statuses.get(id).map(|&(ref status, _)| status) != previous {
+                return false;
+            }
+
+            queues
+                .statuses
+                .insert(id.clone(), (status.clone(), now + ttl));
+            true
+        }))
+    }
+
+    fn create_batch(
+        &self,
+        id: &BatchId,
+        job_ids: &[JobId],
+        callbacks: &[BatchCallback],
+    ) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            queues.batches.insert(
+                id.clone(),
+                StoredBatch {
+                    status: BatchStatus::new(job_ids.len()),
+                    pending: job_ids.iter().cloned().collect(),
+                    callbacks: callbacks.to_vec(),
+                    expires_at: None,
+                },
+            )
+        });
+        Ok(())
+    }
+
+    fn finish_batch_job(
+        &self,
+        id: &BatchId,
+        job_id: &JobId,
+        succeeded: bool,
+        ttl: Duration,
+    ) -> JobQueueResult<Option<(BatchStatus, Vec<EnqueuedJob>)>> {
+        let finished = self.with_queues(|queues| {
+            let (status, callbacks) = {
+                let batch = queues.batch(id)?;
+                if !batch.pending.remove(job_id) {
+                    return None;
+                }
+                let status = batch.status.job_finished(succeeded)?;
+                batch.status = status;
+                if status.is_finished() {
+                    batch.expires_at = Some(Instant::now() + ttl);
+                }
+                let callbacks: Vec<BatchCallback> = batch
+                    .callbacks
+                    .iter()
+                    .filter(|callback| callback.fires_for(&status))
+                    .cloned()
+                    .collect();
+                (status, callbacks)
+            };
+
+            for callback in &callbacks {
+                queues
+                    .jobs
+                    .entry(callback.queue())
+                    .or_insert_with(VecDeque::new)
+                    .push_back(callback.job().clone());
+            }
+            let enqueued: Vec<EnqueuedJob> = callbacks
+                .into_iter()
+                .map(|callback| callback.job().clone())
+                .collect();
+            Some((status, enqueued))
+        });
+
+        if let Some((_, ref enqueued)) = finished {
+            if !enqueued.is_empty() {
+                self.config.shared.job_enqueued.notify_all();
+            }
+        }
+        Ok(finished)
+    }
+
+    fn batch_status(&self, id: &BatchId) -> JobQueueResult<Option<BatchStatus>> {
+        Ok(self.with_queues(|queues| queues.batch(id).map(|batch| batch.status)))
+    }
+
+    fn last_periodic_run(&self, key: &str) -> JobQueueResult<Option<SystemTime>> {
+        Ok(self.with_queues(|queues| queues.periodic_runs.get(key).cloned()))
+    }
+
+    fn claim_periodic_run(
+        &self,
+        key: &str,
+        previous: Option<SystemTime>,
+        run: SystemTime,
+    ) -> JobQueueResult<bool> {
+        Ok(self.with_queues(|queues| {
+            if queues.periodic_runs.get(key).cloned() != previous {
+                return false;
+            }
+
+            queues.periodic_runs.insert(key.to_string(), run);
+            true
+        }))
+    }
+
+    fn acquire_rate_limit(
+        &self,
+        key: &str,
+        limit: u32,
+        period: Duration,
+    ) -> JobQueueResult<Option<Duration>> {
+        Ok(self.with_queues(|queues| {
+            let now = Instant::now();
+            let taken = queues
+                .rate_limits
+                .entry(key.to_string())
+                .or_insert_with(VecDeque::new);
+
+            while taken.front().map_or(false, |&at| at + period <= now) {
+                taken.pop_front();
+            }
+
+            if taken.len() < limit as usize {
+                taken.push_back(now);
+                return None;
+            }
+
+            Some(taken.front().map_or(period, |&oldest| oldest + period - now))
+        }))
+    }
+
+    fn acquire_concurrency_slot(
+        &self,
+        key: &str,
+        id: &JobId,
+        limit: u32,
+        lease: Duration,
+    ) -> JobQueueResult<bool> {
+        Ok(self.with_queues(|queues| {
+            let now = Instant::now();
+            let slots = queues
+                .concurrency_slots
+                .entry(key.to_string())
+                .or_insert_with(HashMap::new);
+            slots.retain(|_, &mut expires_at| expires_at > now);
+
+            if slots.len() >= limit as usize && !slots.contains_key(id) {
+                return false;
+            }
+
+            slots.insert(id.clone(), now + lease);
+            true
+        }))
+    }
+
+    fn release_concurrency_slot(&self, key: &str, id: &JobId) -> JobQueueResult<()> {
+        self.with_queues(|queues| {
+            if let Some(slots) = queues.concurrency_slots.get_mut(key) {
+                slots.remove(id);
+            }
+        });
+        Ok(())
+    }
+
+    fn delete_batch(&self, id: &BatchId) -> JobQueueResult<()> {
+        self.with_queues(|queues| queues.batches.remove(id));
+        Ok(())
+    }
+
+    fn heartbeat(&self, info: &WorkerInfo, ttl: Duration) -> JobQueueResult<()> {
+        let expires_at = Instant::now() + ttl;
+        self.with_queues(|queues| {
+            queues
+                .workers
+                .insert(info.id().to_string(), (info.clone(), expires_at))
+        });
+        Ok(())
+    }
+
+    fn workers(&self) -> JobQueueResult<Vec<WorkerInfo>> {
+        Ok(self.with_queues(|queues| {
+            let now = Instant::now();
+            queues.workers.retain(|_, &mut (_, expires_at)| expires_at > now);
+            queues
+                .workers
+                .values()
+                .map(|&(ref info, _)| info.clone())
+                .collect()
+        }))
+    }
+
+    fn remove_worker(&self, id: &str) -> JobQueueResult<()> {
+        self.with_queues(|queues| queues.workers.remove(id));
+        Ok(())
     }
 }
 
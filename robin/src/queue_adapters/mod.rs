@@ -5,13 +5,24 @@ pub mod redis_queue;
 /// and therefore wont work across processes. Normally you'd only use this during testing.
 pub mod memory_queue;
 
-use config::Config;
-use job::JobName;
+use batch::{BatchCallback, BatchId, BatchStatus};
+use chain::Continuation;
+use config::RetryPolicy;
+use error::{Error, RobinResult};
+use heartbeat::WorkerInfo;
+use job::{Args, JobId};
+use job_status::JobStatus;
+use serde_json::{self, Value};
+use std::collections::BTreeMap;
 use std::marker::Sized;
+use std::time::{Duration, SystemTime};
 use std::{error,
           fmt::{self, Debug}};
 
 /// Trait that represents a backend that can be used to store jobs.
+///
+/// A backend holds one queue for each [`QueueIdentifier`](enum.QueueIdentifier.html). Each queue
+/// also has a set of scheduled jobs that are moved onto the queue once they're due.
 pub trait JobQueue
 where
     Self: Sized,
@@ -19,20 +30,234 @@ where
     /// The type required to configure the queue.
     type Config;
 
-    /// Create a new queue with the given config.
-    fn new(init: &Self::Config) -> JobQueueResult<(Self, Self)>;
+    /// Connect to the backend with the given config.
+    fn new(init: &Self::Config) -> JobQueueResult<Self>;
 
-    /// Push a job into the queue.
-    fn enqueue(&self, enq_job: EnqueuedJob) -> JobQueueResult<()>;
+    /// Push a job into a queue.
+    fn enqueue(&self, iden: QueueIdentifier, enq_job: EnqueuedJob) -> JobQueueResult<()>;
 
-    /// Pull a job from the queue.
-    fn dequeue(&self) -> Result<EnqueuedJob, NoJobDequeued>;
+    /// Push many jobs into a queue, in order.
+    ///
+    /// The default implementation calls `enqueue` for each job. Backends where every call is a
+    /// round trip should override it to push all the jobs at once.
+    fn enqueue_many(&self, iden: QueueIdentifier, enq_jobs: Vec<EnqueuedJob>) -> JobQueueResult<()> {
+        for enq_job in enq_jobs {
+            self.enqueue(iden, enq_job)?;
+        }
+        Ok(())
+    }
 
-    /// Delete all jobs from the queue.
-    fn delete_all(&self) -> JobQueueResult<()>;
+    /// Pull a job from the first of the given queues that has one. Returns the queue the job
+    /// was pulled from together with the job.
+    fn dequeue(
+        &self,
+        idens: &[QueueIdentifier],
+    ) -> Result<(QueueIdentifier, EnqueuedJob), NoJobDequeued>;
+
+    /// Acknowledge that the job last dequeued from a queue has been dealt with.
+    ///
+    /// Backends that keep track of jobs being performed, so they can be recovered after a crash,
+    /// can forget about the job now. The default implementation does nothing.
+    fn ack(&self, _iden: QueueIdentifier) -> JobQueueResult<()> {
+        Ok(())
+    }
+
+    /// Put the job last dequeued from a queue back at the front of the queue, since it won't be
+    /// performed now. The job is acknowledged in the same step, so it's neither lost nor
+    /// duplicated if the process crashes.
+    fn put_back(&self, iden: QueueIdentifier, enq_job: EnqueuedJob) -> JobQueueResult<()>;
+
+    /// Put jobs that were being performed by workers that have crashed back into the queue.
+    /// Returns the number of jobs recovered.
+    ///
+    /// The default implementation does nothing.
+    fn recover(&self, _iden: QueueIdentifier) -> JobQueueResult<usize> {
+        Ok(0)
+    }
+
+    /// Put a job into the scheduled set of a queue. It must not be dequeued before `time`.
+    fn enqueue_at(
+        &self,
+        iden: QueueIdentifier,
+        enq_job: EnqueuedJob,
+        time: SystemTime,
+    ) -> JobQueueResult<()>;
+
+    /// Move the scheduled jobs that are due at `now` into the queue, oldest first.
+    /// Returns the number of jobs moved.
+    fn enqueue_due_jobs(&self, iden: QueueIdentifier, now: SystemTime) -> JobQueueResult<usize>;
+
+    /// Delete all jobs from a queue, including the scheduled ones.
+    fn delete_all(&self, iden: QueueIdentifier) -> JobQueueResult<()>;
+
+    /// Get the number of jobs in a queue.
+    fn size(&self, iden: QueueIdentifier) -> JobQueueResult<usize>;
+
+    /// Get the number of jobs in the scheduled set of a queue.
+    fn scheduled_size(&self, iden: QueueIdentifier) -> JobQueueResult<usize>;
+
+    /// Get all the jobs in a queue without removing them, in the order they'll be dequeued.
+    /// Entries that can't be decoded as jobs are left out.
+    fn jobs(&self, iden: QueueIdentifier) -> JobQueueResult<Vec<EnqueuedJob>>;
+
+    /// Get at most `limit` jobs in a queue without removing them, skipping the first `offset`,
+    /// in the order they'll be dequeued. `offset` and `limit` count entries in the queue, and
+    /// entries that can't be decoded as jobs are left out, so fewer than `limit` jobs can be
+    /// returned even if there are more in the queue.
+    ///
+    /// The default implementation gets all the jobs with `jobs`. Backends should override it if
+    /// they can fetch a range of jobs.
+    fn jobs_range(
+        &self,
+        iden: QueueIdentifier,
+        offset: usize,
+        limit: usize,
+    ) -> JobQueueResult<Vec<EnqueuedJob>> {
+        self.jobs(iden)
+            .map(|jobs| jobs.into_iter().skip(offset).take(limit).collect())
+    }
+
+    /// Remove a job from a queue. Returns `false` if the job wasn't in the queue.
+    fn remove(&self, iden: QueueIdentifier, enq_job: &EnqueuedJob) -> JobQueueResult<bool>;
+
+    /// Atomically remove `enq_job` from the queue `from` and enqueue `moved_job` into `to`.
+    /// Returns `false` if the job wasn't in `from`, in which case nothing is enqueued.
+    fn move_job(
+        &self,
+        from: QueueIdentifier,
+        enq_job: &EnqueuedJob,
+        to: QueueIdentifier,
+        moved_job: EnqueuedJob,
+    ) -> JobQueueResult<bool>;
+
+    /// Store a payload that couldn't be turned into a job, so it can be inspected later.
+    fn poison(&self, poisoned_job: PoisonedJob) -> JobQueueResult<()>;
+
+    /// Get all the poisoned payloads, oldest first.
+    fn poisoned_jobs(&self) -> JobQueueResult<Vec<PoisonedJob>>;
+
+    /// Delete all the poisoned payloads.
+    fn delete_all_poisoned(&self) -> JobQueueResult<()>;
+
+    /// Store the status of a job, replacing the previous one. The status should be forgotten
+    /// once `ttl` has passed.
+    fn set_job_status(&self, id: &JobId, status: &JobStatus, ttl: Duration)
+        -> JobQueueResult<()>;
+
+    /// Store the same status for many jobs, such as jobs that were enqueued together.
+    ///
+    /// The default implementation calls `set_job_status` for each job. Backends should override
+    /// it if they can store the statuses in a single round trip.
+    fn set_job_statuses(
+        &self,
+        ids: &[JobId],
+        status: &JobStatus,
+        ttl: Duration,
+    ) -> JobQueueResult<()> {
+        for id in ids {
+            self.set_job_status(id, status, ttl)?;
+        }
+        Ok(())
+    }
 
-    /// Get the number of jobs in the queue.
-    fn size(&self) -> JobQueueResult<usize>;
+    /// Get the status of a job, if it's known.
+    fn job_status(&self, id: &JobId) -> JobQueueResult<Option<JobStatus>>;
+
+    /// Store the status of a job like `set_job_status`, but only if its current status is still
+    /// `previous`, with `None` meaning the status isn't known. The comparison and the write must
+    /// happen atomically.
+    ///
+    /// Returns `false` if the status was changed by someone else and nothing was stored.
+    fn swap_job_status(
+        &self,
+        id: &JobId,
+        previous: Option<&JobStatus>,
+        status: &JobStatus,
+        ttl: Duration,
+    ) -> JobQueueResult<bool>;
+
+    /// Take the lock for a unique job, holding it for at most `ttl`. Returns `None` if the lock
+    /// was taken, or the ID of the job holding it otherwise.
+    fn lock_unique(&self, key: &str, id: &JobId, ttl: Duration) -> JobQueueResult<Option<JobId>>;
+
+    /// Release the lock for a unique job, if it's still held by the job with the given ID.
+    fn unlock_unique(&self, key: &str, id: &JobId) -> JobQueueResult<()>;
+
+    /// Store a new batch of the jobs with the given IDs together with the callbacks to enqueue
+    /// once they've finished.
+    fn create_batch(
+        &self,
+        id: &BatchId,
+        job_ids: &[JobId],
+        callbacks: &[BatchCallback],
+    ) -> JobQueueResult<()>;
+
+    /// Atomically record that the job with ID `job_id` in a batch has finished, and enqueue the
+    /// callbacks that fire if it was the last one. Returns the updated status together with the
+    /// callback jobs that were enqueued, or `None` if the batch isn't known or the job isn't
+    /// pending, such as when it was already recorded as finished.
+    ///
+    /// Once all the jobs have finished the batch should be forgotten after `ttl`.
+    fn finish_batch_job(
+        &self,
+        id: &BatchId,
+        job_id: &JobId,
+        succeeded: bool,
+        ttl: Duration,
+    ) -> JobQueueResult<Option<(BatchStatus, Vec<EnqueuedJob>)>>;
+
+    /// Get the status of a batch, if it's known.
+    fn batch_status(&self, id: &BatchId) -> JobQueueResult<Option<BatchStatus>>;
+
+    /// Forget a batch, such as one whose jobs couldn't all be enqueued.
+    fn delete_batch(&self, id: &BatchId) -> JobQueueResult<()>;
+
+    /// Get the time of the last claimed run of the periodic job with the given key.
+    fn last_periodic_run(&self, key: &str) -> JobQueueResult<Option<SystemTime>>;
+
+    /// Atomically set the last run of a periodic job to `run`, if it's still `previous`.
+    /// Returns `false` if another process claimed a run in the meantime.
+    ///
+    /// Times only have to be stored with millisecond precision.
+    fn claim_periodic_run(
+        &self,
+        key: &str,
+        previous: Option<SystemTime>,
+        run: SystemTime,
+    ) -> JobQueueResult<bool>;
+
+    /// Take a slot in the rate limit with the given key, which allows `limit` slots within any
+    /// window of length `period`. Returns `None` if a slot was taken, or how long to wait before
+    /// one frees up otherwise.
+    fn acquire_rate_limit(
+        &self,
+        key: &str,
+        limit: u32,
+        period: Duration,
+    ) -> JobQueueResult<Option<Duration>>;
+
+    /// Take one of the `limit` slots of the concurrency limit with the given key for the job with
+    /// the given ID, holding it for at most `lease`. Returns `false` if all slots are taken.
+    fn acquire_concurrency_slot(
+        &self,
+        key: &str,
+        id: &JobId,
+        limit: u32,
+        lease: Duration,
+    ) -> JobQueueResult<bool>;
+
+    /// Give back the slot of the concurrency limit held by the job with the given ID.
+    fn release_concurrency_slot(&self, key: &str, id: &JobId) -> JobQueueResult<()>;
+
+    /// Record the heartbeat of a process running workers. The record is forgotten after `ttl`
+    /// unless another heartbeat with the same ID is recorded.
+    fn heartbeat(&self, info: &WorkerInfo, ttl: Duration) -> JobQueueResult<()>;
+
+    /// The processes running workers whose last heartbeat hasn't expired yet.
+    fn workers(&self) -> JobQueueResult<Vec<WorkerInfo>>;
+
+    /// Forget the heartbeat with the given ID, because its workers have shut down.
+    fn remove_worker(&self, id: &str) -> JobQueueResult<()>;
 }
 
 /// The result type returned by job backends.
@@ -111,18 +336,114 @@ pub enum ErrorOrigin {
     /// The error originated in the `enqueue` method.
     Enqueue,
 
+    /// The error originated in the `enqueue_many` method.
+    EnqueueMany,
+
     /// The error originated in the `dequeue` method.
     Dequeue,
 
+    /// The error originated in the `ack` method.
+    Ack,
+
+    /// The error originated in the `put_back` method.
+    PutBack,
+
+    /// The error originated in the `recover` method.
+    Recover,
+
+    /// The error originated in the `enqueue_at` method.
+    EnqueueAt,
+
+    /// The error originated in the `enqueue_due_jobs` method.
+    EnqueueDueJobs,
+
     /// The error originated in the `delete_all` method.
     DeleteAll,
 
     /// The error originated in the `size` method.
     Size,
+
+    /// The error originated in the `scheduled_size` method.
+    ScheduledSize,
+
+    /// The error originated in the `jobs` method.
+    Jobs,
+
+    /// The error originated in the `jobs_range` method.
+    JobsRange,
+
+    /// The error originated in the `remove` method.
+    Remove,
+
+    /// The error originated in the `move_job` method.
+    MoveJob,
+
+    /// The error originated in the `poison` method.
+    Poison,
+
+    /// The error originated in the `poisoned_jobs` method.
+    PoisonedJobs,
+
+    /// The error originated in the `delete_all_poisoned` method.
+    DeleteAllPoisoned,
+
+    /// The error originated in the `set_job_status` method.
+    SetJobStatus,
+
+    /// The error originated in the `set_job_statuses` method.
+    SetJobStatuses,
+
+    /// The error originated in the `job_status` method.
+    JobStatus,
+
+    /// The error originated in the `swap_job_status` method.
+    SwapJobStatus,
+
+    /// The error originated in the `lock_unique` method.
+    LockUnique,
+
+    /// The error originated in the `unlock_unique` method.
+    UnlockUnique,
+
+    /// The error originated in the `create_batch` method.
+    CreateBatch,
+
+    /// The error originated in the `finish_batch_job` method.
+    FinishBatchJob,
+
+    /// The error originated in the `batch_status` method.
+    BatchStatus,
+
+    /// The error originated in the `delete_batch` method.
+    DeleteBatch,
+
+    /// The error originated in the `last_periodic_run` method.
+    LastPeriodicRun,
+
+    /// The error originated in the `claim_periodic_run` method.
+    ClaimPeriodicRun,
+
+    /// The error originated in the `acquire_rate_limit` method.
+    AcquireRateLimit,
+
+    /// The error originated in the `acquire_concurrency_slot` method.
+    AcquireConcurrencySlot,
+
+    /// The error originated in the `release_concurrency_slot` method.
+    ReleaseConcurrencySlot,
+
+    /// The error originated in the `heartbeat` method.
+    Heartbeat,
+
+    /// The error originated in the `workers` method.
+    Workers,
+
+    /// The error originated in the `remove_worker` method.
+    RemoveWorker,
 }
 
 /// The number of times a job has been retried, if ever.
-#[derive(Deserialize, Serialize, Debug, Copy, Clone)]
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum RetryCount {
     /// The job has never been retried,
     NeverRetried,
@@ -140,33 +461,117 @@ impl RetryCount {
         }
     }
 
-    /// `true` if the retry limit in the config has been reached, `false` otherwise
-    pub fn limit_reached(&self, config: &Config) -> bool {
+    /// The number of the attempt at performing a job with this retry count, starting from 1
+    pub fn attempt(&self) -> u32 {
+        match *self {
+            RetryCount::NeverRetried => 1,
+            RetryCount::Count(n) => n + 1,
+        }
+    }
+
+    /// `true` if the retry limit of the policy has been reached, `false` otherwise
+    pub fn limit_reached(&self, policy: &RetryPolicy) -> bool {
         match *self {
             RetryCount::NeverRetried => false,
-            RetryCount::Count(n) => n > config.retry_count_limit,
+            RetryCount::Count(n) => n > policy.retry_count_limit,
+        }
+    }
+
+    /// How long to wait before performing the job, according to the backoff of the policy
+    pub fn delay(&self, policy: &RetryPolicy) -> Duration {
+        match *self {
+            RetryCount::NeverRetried => Duration::from_secs(0),
+            RetryCount::Count(n) => policy.backoff.delay(n),
         }
     }
 }
 
 /// The data structure that gets serialized and put into Redis.
-#[derive(Deserialize, Serialize, Debug, Builder)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Builder)]
 pub struct EnqueuedJob {
+    #[serde(default = "JobId::missing")]
+    id: JobId,
     name: String,
     args: String,
     retry_count: RetryCount,
+    #[serde(default)]
+    last_error: Option<String>,
+    #[serde(default)]
+    failed_at: Option<SystemTime>,
+    #[serde(default = "SystemTime::now")]
+    enqueued_at: SystemTime,
+    #[serde(default = "SystemTime::now")]
+    first_enqueued_at: SystemTime,
+    #[serde(default)]
+    unique_key: Option<String>,
+    #[serde(default)]
+    batch_id: Option<BatchId>,
+    #[serde(default)]
+    continuations: Vec<Continuation>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    #[serde(skip)]
+    payload: Payload,
+}
+
+/// The JSON a job was decoded from, if it was decoded. Jobs enqueued by earlier versions lack
+/// some fields, so serializing them again gives different JSON. Ignored when comparing jobs.
+#[derive(Debug, Clone, Default)]
+struct Payload(Option<String>);
+
+impl PartialEq for Payload {
+    fn eq(&self, _other: &Payload) -> bool {
+        true
+    }
 }
 
 impl EnqueuedJob {
-    /// Create a new `EnqueuedJob`
+    /// Create a new `EnqueuedJob` with a new ID
     pub fn new(name: &str, args: &str, retry_count: RetryCount) -> Self {
+        let now = SystemTime::now();
         EnqueuedJob {
+            id: JobId::new(),
             name: name.to_string(),
             args: args.to_string(),
             retry_count: retry_count,
+            last_error: None,
+            failed_at: None,
+            enqueued_at: now,
+            first_enqueued_at: now,
+            unique_key: None,
+            batch_id: None,
+            continuations: vec![],
+            metadata: BTreeMap::new(),
+            payload: Payload::default(),
         }
     }
 
+    #[doc(hidden)]
+    pub fn decode(data: &str) -> serde_json::Result<EnqueuedJob> {
+        serde_json::from_str(data).map(|enq_job: EnqueuedJob| EnqueuedJob {
+            id: if enq_job.id.is_missing() {
+                JobId::from_payload(data)
+            } else {
+                enq_job.id
+            },
+            payload: Payload(Some(data.to_string())),
+            ..enq_job
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn encode(&self) -> String {
+        match self.payload.0 {
+            Some(ref data) => data.clone(),
+            None => json!(self).to_string(),
+        }
+    }
+
+    /// Get the ID
+    pub fn id(&self) -> &JobId {
+        &self.id
+    }
+
     /// Get the name
     pub fn name(&self) -> &str {
         &self.name
@@ -177,10 +582,185 @@ impl EnqueuedJob {
         &self.args
     }
 
+    /// Get the arguments decoded into the JSON value the job receives
+    pub fn decoded_args(&self) -> RobinResult<Value> {
+        let args: Args = serde_json::from_str(&self.args)?;
+        serde_json::from_str(args.json()).map_err(Error::from)
+    }
+
     /// Get the retry count
     pub fn retry_count(&self) -> &RetryCount {
         &self.retry_count
     }
+
+    /// Get the error from the last time the job failed, if it has ever failed
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_ref().map(|error| error.as_str())
+    }
+
+    /// Get the time the job last failed, if it has ever failed
+    pub fn failed_at(&self) -> Option<SystemTime> {
+        self.failed_at
+    }
+
+    /// Get the time the job was last put into a queue
+    pub fn enqueued_at(&self) -> SystemTime {
+        self.enqueued_at
+    }
+
+    /// Get the time the job was first put into a queue. Unlike `enqueued_at` this isn't updated
+    /// when the job is retried or requeued.
+    pub fn first_enqueued_at(&self) -> SystemTime {
+        self.first_enqueued_at
+    }
+
+    /// Get the key of the lock held by the job, if it's unique
+    pub fn unique_key(&self) -> Option<&str> {
+        self.unique_key.as_ref().map(|key| key.as_str())
+    }
+
+    /// Record that the job holds the lock with the given key.
+    pub fn with_unique_key(self, key: String) -> Self {
+        EnqueuedJob {
+            unique_key: Some(key),
+            ..self
+        }
+    }
+
+    /// Get the ID of the batch the job belongs to, if any
+    pub fn batch_id(&self) -> Option<&BatchId> {
+        self.batch_id.as_ref()
+    }
+
+    /// Record that the job belongs to the batch with the given ID.
+    pub fn with_batch_id(self, id: BatchId) -> Self {
+        EnqueuedJob {
+            batch_id: Some(id),
+            ..self
+        }
+    }
+
+    /// Get the jobs to enqueue one after the other once this job has succeeded
+    pub fn continuations(&self) -> &[Continuation] {
+        &self.continuations
+    }
+
+    /// Record the jobs to enqueue one after the other once this job has succeeded.
+    pub fn with_continuations(self, continuations: Vec<Continuation>) -> Self {
+        EnqueuedJob {
+            continuations,
+            ..self
+        }
+    }
+
+    /// Get a value stored with the job, usually by
+    /// [`ClientMiddleware`](../middleware/trait.ClientMiddleware.html)
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(|value| value.as_str())
+    }
+
+    /// Store a value with the job, which is kept when the job is retried or requeued.
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Record that the job is being put into a queue again.
+    pub fn requeued(self) -> Self {
+        EnqueuedJob {
+            enqueued_at: SystemTime::now(),
+            ..self
+        }
+    }
+
+    /// Record that performing the job failed with the given error. This increments the retry
+    /// count.
+    pub fn failed(self, error: String) -> Self {
+        EnqueuedJob {
+            retry_count: self.retry_count.increment(),
+            ..self.with_error(error)
+        }
+    }
+
+    /// Record an error for the job without counting it as a failed attempt.
+    pub fn with_error(self, error: String) -> Self {
+        EnqueuedJob {
+            last_error: Some(error),
+            failed_at: Some(SystemTime::now()),
+            ..self
+        }
+    }
+
+    /// Reset the retry count and the failure information, so the job can be performed again
+    /// as if it was new.
+    pub fn reset(self) -> Self {
+        EnqueuedJob {
+            retry_count: RetryCount::NeverRetried,
+            last_error: None,
+            failed_at: None,
+            ..self.requeued()
+        }
+    }
+}
+
+/// A payload that couldn't be turned into a job, together with what went wrong.
+///
+/// Workers move these out of the way rather than crashing, so producers and workers running
+/// different versions of your jobs don't take each other down.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PoisonedJob {
+    payload: String,
+    reason: PoisonReason,
+    error: String,
+    poisoned_at: SystemTime,
+}
+
+impl PoisonedJob {
+    /// Create a new `PoisonedJob`. `error` describes what failed to parse.
+    pub fn new(payload: &str, reason: PoisonReason, error: &str) -> Self {
+        PoisonedJob {
+            payload: payload.to_string(),
+            reason,
+            error: error.to_string(),
+            poisoned_at: SystemTime::now(),
+        }
+    }
+
+    /// Get the raw payload. Bytes that weren't valid UTF-8 are replaced with `U+FFFD`.
+    pub fn payload(&self) -> &str {
+        &self.payload
+    }
+
+    /// Get the reason the payload was poisoned
+    pub fn reason(&self) -> PoisonReason {
+        self.reason
+    }
+
+    /// Get the description of what failed to parse
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+
+    /// Get the time the payload was poisoned
+    pub fn poisoned_at(&self) -> SystemTime {
+        self.poisoned_at
+    }
+}
+
+/// The ways a payload can fail to be turned into a job.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PoisonReason {
+    /// The payload wasn't valid UTF-8.
+    InvalidUtf8,
+
+    /// The payload wasn't a valid JSON encoded `EnqueuedJob`.
+    InvalidJob,
+
+    /// The arguments of the job weren't valid JSON encoded `Args`.
+    InvalidArgs,
+
+    /// The backend returned a value of a type it should never contain.
+    UnexpectedValue,
 }
 
 /// Reasons why attempting to dequeue a job didn't yield a job.
@@ -192,8 +772,13 @@ pub enum NoJobDequeued {
     /// Because there some error.
     BecauseError(JobQueueError),
 
-    /// The job name wasn't known by the lookup function.
-    BecauseUnknownJob(JobName),
+    /// The job name wasn't known by the lookup function. Contains the queue the job was
+    /// dequeued from and the job.
+    BecauseUnknownJob(QueueIdentifier, EnqueuedJob),
+
+    /// The payload pulled from the queue couldn't be turned into a job. Contains the queue the
+    /// payload was dequeued from.
+    BecauseMalformedPayload(QueueIdentifier, PoisonedJob),
 }
 
 impl<T: 'static + JobQueueErrorInformation> From<T> for NoJobDequeued {
@@ -203,12 +788,53 @@ impl<T: 'static + JobQueueErrorInformation> From<T> for NoJobDequeued {
 }
 
 /// The different queues supported by Robin.
-#[derive(EachVariant, Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum QueueIdentifier {
     /// The main queue all new jobs are put into.
     Main,
 
-    /// If a job from the main queue fails it gets put into the retry queue
-    /// and retried later.
+    /// Failed jobs used to be put into the retry queue. They're now retried from their own
+    /// queue, but the workers still perform the jobs left here by earlier versions.
     Retry,
+
+    /// Jobs that have failed more than the retry limit end up here, together with their last
+    /// error. They stay here until they're requeued or deleted.
+    Dead,
+
+    /// A queue declared by jobs with `jobs!`, such as `SendEmail(Args) => "mailers"`.
+    /// See [`Config::queues`](../config/struct.Config.html#structfield.queues) for how they're
+    /// processed.
+    Named(&'static str),
+}
+
+impl QueueIdentifier {
+    /// Get the name of the queue, such as `"main"` or the name of a named queue.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            QueueIdentifier::Main => "main",
+            QueueIdentifier::Retry => "retry",
+            QueueIdentifier::Dead => "dead",
+            QueueIdentifier::Named(name) => name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_jobs_encode_to_their_payload() {
+        let data = r#"{"name":"SendEmail","args":"{\"json\":\"1\"}","retry_count":"NeverRetried"}"#;
+
+        let first = EnqueuedJob::decode(data).unwrap();
+        let second = EnqueuedJob::decode(data).unwrap();
+        assert_eq!(first.id(), second.id());
+        assert_eq!(first.id().as_str(), "legacy:8dbbd1d1dcc9c7f9");
+        assert_eq!(first.encode(), data);
+        assert_eq!(second.encode(), data);
+
+        let new_job = EnqueuedJob::new("SendEmail", "{}", RetryCount::NeverRetried);
+        assert_eq!(EnqueuedJob::decode(&new_job.encode()).unwrap(), new_job);
+    }
 }
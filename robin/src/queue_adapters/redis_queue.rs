@@ -1,22 +1,450 @@
 use super::*;
+use batch::{BatchCallback, BatchId, BatchStatus};
 use redis;
-use redis::{Client, Commands};
+use redis::{Client, Commands, PipelineCommands};
 use serde_json;
 use std::default::Default;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 
 /// A queue backend the persists the jobs in Redis.
+///
+/// Jobs are pushed onto the tail of a list and popped from the head. With `reliable_fetch` the
+/// lists are used the other way around, since `BRPOPLPUSH` can only pop from the tail.
 pub struct RedisQueue {
+    id: String,
     redis_con: Arc<redis::Connection>,
     redis_url: String,
-    key: String,
+    namespace: String,
     timeout: usize,
+    reliable_fetch: bool,
+    orphan_timeout: usize,
+    heartbeat_keeper: Mutex<Option<Sender<()>>>,
 }
 
 impl RedisQueue {
-    fn key(&self) -> String {
-        self.key.clone()
+    fn key(&self, iden: QueueIdentifier) -> String {
+        let name = match iden {
+            QueueIdentifier::Main => "main".to_string(),
+            QueueIdentifier::Retry => "retry".to_string(),
+            QueueIdentifier::Dead => "dead".to_string(),
+            QueueIdentifier::Named(name) => format!("queue_{}", name),
+        };
+        format!("{}_{}", name, self.namespace)
+    }
+
+    /// The command that pushes jobs onto the end of a list they're dequeued from last.
+    fn push_back_command(&self) -> &'static str {
+        if self.reliable_fetch {
+            "LPUSH"
+        } else {
+            "RPUSH"
+        }
+    }
+
+    /// The `count` that makes `LREM` remove the occurrence of a job that would be dequeued
+    /// first.
+    fn remove_first_count(&self) -> isize {
+        if self.reliable_fetch {
+            -1
+        } else {
+            1
+        }
+    }
+
+    /// Decode the jobs in a list in the order they'll be dequeued, skipping entries that aren't
+    /// valid jobs. Those are only found when they're dequeued, and then moved to the poison
+    /// queue.
+    fn decode_jobs(&self, mut data: Vec<String>) -> Vec<EnqueuedJob> {
+        if self.reliable_fetch {
+            data.reverse();
+        }
+
+        data.iter()
+            .filter_map(|data| match EnqueuedJob::decode(data) {
+                Ok(enq_job) => Some(enq_job),
+                Err(err) => {
+                    warn!("Skipped malformed payload while listing jobs ({})\n{}", err, data);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn scheduled_key(&self, iden: QueueIdentifier) -> String {
+        format!("{}_scheduled", self.key(iden))
+    }
+
+    /// The set containing the names of the processing lists for a queue.
+    fn processing_set_key(&self, iden: QueueIdentifier) -> String {
+        format!("{}_processing", self.key(iden))
+    }
+
+    /// The list holding the job this queue is currently processing.
+    fn processing_key(&self, iden: QueueIdentifier) -> String {
+        format!("{}_{}", self.processing_set_key(iden), self.id)
+    }
+
+    fn heartbeat_key(&self, id: &str) -> String {
+        format!("heartbeat_{}_{}", id, self.namespace)
+    }
+
+    fn beat(&self, origin: ErrorOrigin) -> JobQueueResult<()> {
+        let _: () = self.redis_con
+            .set_ex(&self.heartbeat_key(&self.id), 1, self.orphan_timeout)
+            .map_err(|e| (e, origin))?;
+        Ok(())
+    }
+
+    /// Make sure the heartbeat keeps being recorded in the background, so the job being
+    /// performed isn't recovered while it's still running.
+    fn keep_beating(&self) {
+        let mut keeper = self.heartbeat_keeper.lock().expect("mutex was poisoned");
+        if keeper.is_none() {
+            *keeper = Some(self.spawn_heartbeat_keeper());
+        }
+    }
+
+    /// Record the heartbeat every third of `orphan_timeout` from a separate connection, until
+    /// the returned sender is dropped along with the queue.
+    fn spawn_heartbeat_keeper(&self) -> Sender<()> {
+        let (stop, stopped) = channel::<()>();
+        let url = self.redis_url.clone();
+        let key = self.heartbeat_key(&self.id);
+        let ttl = self.orphan_timeout;
+        let interval = Duration::from_secs((ttl / 3).max(1) as u64);
+
+        thread::spawn(move || {
+            let con = match Client::open(url.as_ref()).and_then(|client| client.get_connection()) {
+                Ok(con) => con,
+                Err(err) => {
+                    error!("Failed to connect to Redis for recording heartbeats\n{:?}", err);
+                    return;
+                }
+            };
+
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let result: redis::RedisResult<()> = con.set_ex(&key, 1, ttl);
+                if let Err(err) = result {
+                    error!("Failed to record heartbeat\n{:?}", err);
+                }
+            }
+        });
+
+        stop
+    }
+
+    fn job_status_key(&self, id: &JobId) -> String {
+        format!("status_{}_{}", id, self.namespace)
+    }
+
+    fn unique_key(&self, key: &str) -> String {
+        format!("unique_{}_{}", key, self.namespace)
+    }
+
+    fn batch_key(&self, id: &BatchId) -> String {
+        format!("batch_{}_{}", id, self.namespace)
+    }
+
+    /// The set of the IDs of the jobs in a batch that haven't finished yet.
+    fn batch_pending_key(&self, id: &BatchId) -> String {
+        format!("batch_pending_{}_{}", id, self.namespace)
+    }
+
+    fn rate_limit_key(&self, key: &str) -> String {
+        format!("rate_limit_{}_{}", key, self.namespace)
+    }
+
+    fn concurrency_key(&self, key: &str) -> String {
+        format!("concurrency_{}_{}", key, self.namespace)
+    }
+
+    fn worker_key(&self, id: &str) -> String {
+        format!("worker_{}_{}", id, self.namespace)
+    }
+
+    fn workers_key(&self) -> String {
+        format!("workers_{}", self.namespace)
+    }
+
+    fn periodic_key(&self, key: &str) -> String {
+        format!("periodic_{}_{}", key, self.namespace)
+    }
+
+    fn poison_key(&self) -> String {
+        format!("poison_{}", self.namespace)
+    }
+
+    fn parse_job(
+        &self,
+        iden: QueueIdentifier,
+        value: &redis::Value,
+    ) -> Result<(QueueIdentifier, EnqueuedJob), NoJobDequeued> {
+        let poisoned = |payload: &str, reason, error: &str| {
+            NoJobDequeued::BecauseMalformedPayload(iden, PoisonedJob::new(payload, reason, error))
+        };
+
+        match *value {
+            redis::Value::Data(ref data) => {
+                let data = String::from_utf8(data.to_vec()).map_err(|e| {
+                    poisoned(
+                        &String::from_utf8_lossy(data),
+                        PoisonReason::InvalidUtf8,
+                        &e.to_string(),
+                    )
+                })?;
+                EnqueuedJob::decode(&data)
+                    .map(|enq_job| (iden, enq_job))
+                    .map_err(|e| poisoned(&data, PoisonReason::InvalidJob, &e.to_string()))
+            }
+
+            redis::Value::Nil => Err(NoJobDequeued::BecauseTimeout),
+
+            ref other => Err(poisoned(
+                &format!("{:?}", other),
+                PoisonReason::UnexpectedValue,
+                "Expected a string or nil from Redis",
+            )),
+        }
+    }
+
+    fn reliable_dequeue(
+        &self,
+        idens: &[QueueIdentifier],
+    ) -> Result<(QueueIdentifier, EnqueuedJob), NoJobDequeued> {
+        self.beat(ErrorOrigin::Dequeue).map_err(NoJobDequeued::BecauseError)?;
+        self.keep_beating();
+
+        for &iden in idens {
+            let _: () = self.redis_con
+                .sadd(&self.processing_set_key(iden), self.processing_key(iden))
+                .map_err(|e| NoJobDequeued::from((e, ErrorOrigin::Dequeue)))?;
+        }
+
+        let job = if idens.len() == 1 {
+            self.blocking_move_to_processing(idens[0], self.timeout)
+        } else {
+            self.poll_move_to_processing(idens)
+        }?;
+
+        self.beat(ErrorOrigin::Dequeue).map_err(NoJobDequeued::BecauseError)?;
+        Ok(job)
+    }
+
+    /// `BRPOPLPUSH` can only wait on a single list, so with several queues we check each of them
+    /// in order and then wait a second on the first one, until the timeout is hit.
+    fn poll_move_to_processing(
+        &self,
+        idens: &[QueueIdentifier],
+    ) -> Result<(QueueIdentifier, EnqueuedJob), NoJobDequeued> {
+        let deadline = Instant::now() + Duration::from_secs(self.timeout as u64);
+
+        loop {
+            for &iden in idens {
+                let value: redis::Value = self.redis_con
+                    .rpoplpush(&self.key(iden), &self.processing_key(iden))
+                    .map_err(|e| NoJobDequeued::from((e, ErrorOrigin::Dequeue)))?;
+
+                if value != redis::Value::Nil {
+                    return self.parse_job(iden, &value);
+                }
+            }
+
+            match self.blocking_move_to_processing(idens[0], 1) {
+                Err(NoJobDequeued::BecauseTimeout) => if Instant::now() >= deadline {
+                    return Err(NoJobDequeued::BecauseTimeout);
+                },
+                other => return other,
+            }
+        }
+    }
+
+    fn blocking_move_to_processing(
+        &self,
+        iden: QueueIdentifier,
+        timeout: usize,
+    ) -> Result<(QueueIdentifier, EnqueuedJob), NoJobDequeued> {
+        let value: redis::Value = self.redis_con
+            .brpoplpush(&self.key(iden), &self.processing_key(iden), timeout)
+            .map_err(|e| NoJobDequeued::from((e, ErrorOrigin::Dequeue)))?;
+
+        self.parse_job(iden, &value)
+    }
+}
+
+/// Moves the members of the sorted set `KEYS[1]` with a score of at most `ARGV[1]` onto the list
+/// `KEYS[2]` with the push command `ARGV[2]`. Members are prefixed with a unique id followed by
+/// `:` which gets stripped off.
+const ENQUEUE_DUE_JOBS_SCRIPT: &str = r"
+local jobs = redis.call('zrangebyscore', KEYS[1], '-inf', ARGV[1])
+for _, job in ipairs(jobs) do
+    redis.call('zrem', KEYS[1], job)
+    redis.call(ARGV[2], KEYS[2], string.sub(job, string.find(job, ':', 1, true) + 1))
+end
+return #jobs
+";
+
+/// Moves the jobs in the processing list `KEYS[1]` back onto the tail of the queue `KEYS[2]`, so
+/// they're the next to be dequeued. Then removes the processing list from the set `KEYS[3]`.
+const RECOVER_SCRIPT: &str = r"
+local count = 0
+while true do
+    local job = redis.call('rpop', KEYS[1])
+    if not job then
+        break
+    end
+    redis.call('rpush', KEYS[2], job)
+    count = count + 1
+end
+redis.call('srem', KEYS[3], KEYS[1])
+return count
+";
+
+/// Removes the job `ARGV[1]` from the processing list `KEYS[1]` and pushes it back onto the tail
+/// of the queue `KEYS[2]`, so it's the next to be dequeued.
+const PUT_BACK_SCRIPT: &str = r"
+redis.call('lrem', KEYS[1], 0, ARGV[1])
+redis.call('rpush', KEYS[2], ARGV[1])
+return 0
+";
+
+/// Removes the job `ARGV[1]` from the list `KEYS[1]` with `LREM` count `ARGV[3]` and, if it was
+/// there, pushes the job `ARGV[2]` onto the list `KEYS[2]` with the push command `ARGV[4]`.
+/// Returns 1 if the job was moved.
+const MOVE_JOB_SCRIPT: &str = r"
+if redis.call('lrem', KEYS[1], ARGV[3], ARGV[1]) == 0 then
+    return 0
+end
+redis.call(ARGV[4], KEYS[2], ARGV[2])
+return 1
+";
+
+/// Sets `KEYS[1]` to `ARGV[2]` with a TTL of `ARGV[3]` seconds, if it's still set to `ARGV[1]`.
+/// An empty `ARGV[1]` means the key must not exist. Returns 1 if the key was set, 0 otherwise.
+const SWAP_JOB_STATUS_SCRIPT: &str = r"
+local current = redis.call('get', KEYS[1]) or ''
+if current ~= ARGV[1] then
+    return 0
+end
+redis.call('set', KEYS[1], ARGV[2], 'EX', ARGV[3])
+return 1
+";
+
+/// Sets `KEYS[1]` to the job ID `ARGV[1]` with a TTL of `ARGV[2]` milliseconds, unless it's
+/// already set. Returns the ID already set, if any.
+const LOCK_UNIQUE_SCRIPT: &str = r"
+if redis.call('set', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return false
+end
+return redis.call('get', KEYS[1])
+";
+
+/// Deletes `KEYS[1]` if it's still set to the job ID `ARGV[1]`.
+const UNLOCK_UNIQUE_SCRIPT: &str = r"
+if redis.call('get', KEYS[1]) == ARGV[1] then
+    redis.call('del', KEYS[1])
+end
+return 0
+";
+
+/// Records that the job `ARGV[1]` in the batch `KEYS[1]` has finished by removing it from the
+/// set of pending jobs `KEYS[2]` and moving it from `pending` to the field `ARGV[2]`. Does
+/// nothing if the job isn't pending, so recording the same job twice is harmless.
+///
+/// Once no jobs are pending the callbacks that fire are pushed onto their queues with the
+/// command `ARGV[4]`, and the batch expires after `ARGV[3]` seconds. Returns the `total`,
+/// `pending`, `succeeded`, and `failed` counts and a list of the enqueued callback jobs, or
+/// `false` if the job wasn't pending.
+const FINISH_BATCH_JOB_SCRIPT: &str = r"
+if redis.call('srem', KEYS[2], ARGV[1]) == 0 then
+    return false
+end
+redis.call('hincrby', KEYS[1], 'pending', -1)
+redis.call('hincrby', KEYS[1], ARGV[2], 1)
+local counts = redis.call('hmget', KEYS[1], 'total', 'pending', 'succeeded', 'failed')
+local enqueued = {}
+if tonumber(counts[2]) == 0 then
+    local callbacks = cjson.decode(redis.call('hget', KEYS[1], 'callbacks'))
+    for _, callback in ipairs(callbacks) do
+        if callback.event == 'Complete' or tonumber(counts[4]) == 0 then
+            redis.call(ARGV[4], callback.key, callback.job)
+            table.insert(enqueued, callback.job)
+        end
+    end
+    redis.call('expire', KEYS[1], ARGV[3])
+end
+return {counts[1], counts[2], counts[3], counts[4], enqueued}
+";
+
+/// Sets `KEYS[1]` to `ARGV[2]` if it's currently `ARGV[1]`, where an empty string means the key
+/// isn't set. Returns 1 if the key was set and 0 otherwise.
+const CLAIM_PERIODIC_RUN_SCRIPT: &str = r"
+local current = redis.call('get', KEYS[1]) or ''
+if current ~= ARGV[1] then
+    return 0
+end
+redis.call('set', KEYS[1], ARGV[2])
+return 1
+";
+
+/// A sliding window rate limit. `KEYS[1]` is a sorted set of the slots taken, scored by the time
+/// in milliseconds they were taken according to the Redis server, so workers with skewed clocks
+/// share the same window. Allows `ARGV[1]` slots within `ARGV[2]` milliseconds, and takes one for
+/// the member `ARGV[3]` if possible. Returns 0 if a slot was taken, or how many milliseconds to
+/// wait before one frees up otherwise.
+///
+/// `TIME` isn't deterministic, so the script replicates its writes rather than itself.
+const ACQUIRE_RATE_LIMIT_SCRIPT: &str = r"
+redis.replicate_commands()
+local time = redis.call('time')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local period = tonumber(ARGV[2])
+redis.call('zremrangebyscore', KEYS[1], '-inf', now - period)
+if redis.call('zcard', KEYS[1]) < tonumber(ARGV[1]) then
+    redis.call('zadd', KEYS[1], now, ARGV[3])
+    redis.call('pexpire', KEYS[1], period)
+    return 0
+end
+local oldest = redis.call('zrange', KEYS[1], 0, 0, 'WITHSCORES')
+return math.max(tonumber(oldest[2]) + period - now, 1)
+";
+
+/// A semaphore with leases. `KEYS[1]` is a sorted set of the job IDs holding a slot, scored by the
+/// time in milliseconds their lease expires. Removes the leases that expired before `ARGV[1]`,
+/// then takes one of the `ARGV[2]` slots for the job `ARGV[3]` with a lease of `ARGV[4]`
+/// milliseconds if one is free. Returns 1 if a slot was taken and 0 otherwise.
+const ACQUIRE_CONCURRENCY_SLOT_SCRIPT: &str = r"
+local now = tonumber(ARGV[1])
+local lease = tonumber(ARGV[4])
+redis.call('zremrangebyscore', KEYS[1], '-inf', now)
+local full = redis.call('zcard', KEYS[1]) >= tonumber(ARGV[2])
+if full and not redis.call('zscore', KEYS[1], ARGV[3]) then
+    return 0
+end
+redis.call('zadd', KEYS[1], now + lease, ARGV[3])
+redis.call('pexpire', KEYS[1], lease)
+return 1
+";
+
+/// The most values pushed by a single command in `enqueue_many`, so huge batches don't produce
+/// a single huge command.
+const ENQUEUE_MANY_CHUNK_SIZE: usize = 1_000;
+
+fn unix_millis(time: SystemTime) -> u64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() * 1_000 + u64::from(duration.subsec_nanos() / 1_000_000),
+        Err(_) => 0,
+    }
+}
+
+fn unix_timestamp(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9,
+        Err(_) => 0.0,
     }
 }
 
@@ -33,13 +461,38 @@ pub struct RedisConfig {
     pub namespace: String,
 
     /// The number of seconds the worker will block while waiting for a new job to be enqueued.
+    /// Keep it shorter than
+    /// [`Config::shutdown_timeout`](../../config/struct.Config.html#structfield.shutdown_timeout),
+    /// so waiting workers have stopped before the process exits.
+    ///
+    /// Default is 5.
     pub timeout: usize,
+
+    /// Keep jobs in Redis while they're being performed, so they aren't lost if the worker
+    /// crashes. The jobs of workers that crashed are put back into their queue when a new worker
+    /// starts. This means a job might be performed more than once.
+    ///
+    /// The lists are used the other way around with this enabled, so all the processes sharing
+    /// a queue must agree on it. Jobs that are already queued when turning it on are performed
+    /// newest first.
+    ///
+    /// Default is `false`.
+    pub reliable_fetch: bool,
+
+    /// The number of seconds after which a worker that hasn't recorded a heartbeat is considered
+    /// crashed, when `reliable_fetch` is enabled. Workers record a heartbeat every third of this
+    /// from a background thread, so jobs may run for longer.
+    ///
+    /// Default is one hour.
+    pub orphan_timeout: usize,
 }
 
 impl Default for RedisConfig {
     fn default() -> RedisConfig {
         RedisConfig {
-            timeout: 30,
+            timeout: 5,
+            reliable_fetch: false,
+            orphan_timeout: 60 * 60,
             namespace: "robin_".to_string(),
             url: "redis://127.0.0.1/".to_string(),
         }
@@ -50,85 +503,624 @@ impl JobQueue for RedisQueue {
     type Config = RedisConfig;
 
     /// Create a new `RedisQueue` using the given config
-    fn new(init: &RedisConfig) -> JobQueueResult<(Self, Self)> {
+    fn new(init: &RedisConfig) -> JobQueueResult<Self> {
         let client = Client::open(init.url.as_ref()).map_err(|e| (e, ErrorOrigin::Initialization))?;
 
         let con = client
             .get_connection()
             .map_err(|e| (e, ErrorOrigin::Initialization))?;
-        let con = Arc::new(con);
-
-        let main_q = RedisQueue {
-            redis_con: Arc::clone(&con),
-            redis_url: init.url.to_string(),
-            key: format!("{}_{}", "main", init.namespace.to_string()),
-            timeout: init.timeout,
-        };
 
-        let retry_q = RedisQueue {
-            redis_con: Arc::clone(&con),
+        Ok(RedisQueue {
+            id: Uuid::new_v4().hyphenated().to_string(),
+            redis_con: Arc::new(con),
             redis_url: init.url.to_string(),
-            key: format!("{}_{}", "retry", init.namespace.to_string()),
+            namespace: init.namespace.to_string(),
             timeout: init.timeout,
-        };
-
-        Ok((main_q, retry_q))
+            reliable_fetch: init.reliable_fetch,
+            orphan_timeout: init.orphan_timeout,
+            heartbeat_keeper: Mutex::new(None),
+        })
     }
 
     /// Put a job into a queue
-    fn enqueue(&self, enq_job: EnqueuedJob) -> JobQueueResult<()> {
+    fn enqueue(&self, iden: QueueIdentifier, enq_job: EnqueuedJob) -> JobQueueResult<()> {
         let data: String = json!(enq_job).to_string();
-        let _: () = self.redis_con
-            .rpush(&self.key(), data)
+        let _: () = redis::cmd(self.push_back_command())
+            .arg(self.key(iden))
+            .arg(data)
+            .query(&*self.redis_con)
             .map_err(|e| (e, ErrorOrigin::Enqueue))?;
 
         Ok(())
     }
 
-    /// Pull a job out of the queue. This will block for `timeout` seconds if the queue is empty.
-    fn dequeue(&self) -> Result<EnqueuedJob, NoJobDequeued> {
+    /// Put many jobs into a queue with a single round trip. The pushes are sent in one atomic
+    /// pipeline, so either all the jobs are enqueued or none are.
+    fn enqueue_many(&self, iden: QueueIdentifier, enq_jobs: Vec<EnqueuedJob>) -> JobQueueResult<()> {
+        if enq_jobs.is_empty() {
+            return Ok(());
+        }
+
+        let key = self.key(iden);
+        let data: Vec<String> = enq_jobs
+            .iter()
+            .map(|enq_job| json!(enq_job).to_string())
+            .collect();
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for chunk in data.chunks(ENQUEUE_MANY_CHUNK_SIZE) {
+            pipe.cmd(self.push_back_command()).arg(&key).arg(chunk).ignore();
+        }
+        let _: () = pipe.query(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::EnqueueMany))?;
+
+        Ok(())
+    }
+
+    /// Pull a job out of the first of the queues that has one. This will block for `timeout`
+    /// seconds if all the queues are empty.
+    ///
+    /// With `reliable_fetch` the job is atomically moved into a processing list where it stays
+    /// until it's acknowledged.
+    fn dequeue(
+        &self,
+        idens: &[QueueIdentifier],
+    ) -> Result<(QueueIdentifier, EnqueuedJob), NoJobDequeued> {
+        if self.reliable_fetch {
+            return self.reliable_dequeue(idens);
+        }
+
+        let keys: Vec<String> = idens.iter().map(|&iden| self.key(iden)).collect();
         let timeout_in_seconds = self.timeout;
-        let bulk: Vec<redis::Value> = self.redis_con
-            .blpop(&self.key(), timeout_in_seconds)
+        let popped: Option<(String, redis::Value)> = self.redis_con
+            .blpop(keys.clone(), timeout_in_seconds)
             .map_err(|e| NoJobDequeued::from((e, ErrorOrigin::Dequeue)))?;
 
-        match bulk.get(1) {
-            Some(&redis::Value::Data(ref data)) => {
-                let data =
-                    String::from_utf8(data.to_vec()).expect("Didn't get valid UTF-8 from Redis");
-                serde_json::from_str(&data)
-                    .map_err(|e| NoJobDequeued::from((e, ErrorOrigin::Dequeue)))
+        match popped {
+            Some((key, value)) => {
+                let iden = keys.iter()
+                    .position(|k| *k == key)
+                    .map(|idx| idens[idx])
+                    .expect("Redis popped from a list that wasn't asked for");
+                self.parse_job(iden, &value)
             }
-
             None => Err(NoJobDequeued::BecauseTimeout),
+        }
+    }
+
+    /// Delete the processing list, if `reliable_fetch` is enabled.
+    fn ack(&self, iden: QueueIdentifier) -> JobQueueResult<()> {
+        if self.reliable_fetch {
+            let _: () = self.redis_con
+                .del(&self.processing_key(iden))
+                .map_err(|e| (e, ErrorOrigin::Ack))?;
+        }
+        Ok(())
+    }
 
-            _ => panic!("TODO"),
+    /// Push the job back onto the end of the list it's popped from. With `reliable_fetch` it's
+    /// also removed from the processing list in the same script.
+    fn put_back(&self, iden: QueueIdentifier, enq_job: EnqueuedJob) -> JobQueueResult<()> {
+        if self.reliable_fetch {
+            let _: () = redis::Script::new(PUT_BACK_SCRIPT)
+                .key(self.processing_key(iden))
+                .key(self.key(iden))
+                .arg(enq_job.encode())
+                .invoke(&*self.redis_con)
+                .map_err(|e| (e, ErrorOrigin::PutBack))?;
+        } else {
+            let _: () = self.redis_con
+                .lpush(&self.key(iden), enq_job.encode())
+                .map_err(|e| (e, ErrorOrigin::PutBack))?;
         }
+        Ok(())
+    }
+
+    /// Move the jobs from the processing lists of workers that haven't been seen for
+    /// `orphan_timeout` seconds back into the queue.
+    fn recover(&self, iden: QueueIdentifier) -> JobQueueResult<usize> {
+        let processing_lists: Vec<String> = self.redis_con
+            .smembers(&self.processing_set_key(iden))
+            .map_err(|e| (e, ErrorOrigin::Recover))?;
+
+        let prefix = format!("{}_", self.processing_set_key(iden));
+        let mut count = 0;
+
+        for processing_list in processing_lists {
+            if !processing_list.starts_with(&prefix) {
+                continue;
+            }
+
+            let worker_id = &processing_list[prefix.len()..];
+            let worker_alive: bool = self.redis_con
+                .exists(&self.heartbeat_key(worker_id))
+                .map_err(|e| (e, ErrorOrigin::Recover))?;
+
+            if worker_alive {
+                continue;
+            }
+
+            let recovered: usize = redis::Script::new(RECOVER_SCRIPT)
+                .key(&processing_list)
+                .key(self.key(iden))
+                .key(self.processing_set_key(iden))
+                .invoke(&*self.redis_con)
+                .map_err(|e| (e, ErrorOrigin::Recover))?;
+            count += recovered;
+        }
+
+        Ok(count)
+    }
+
+    /// Put a job into a sorted set scored by `time`.
+    fn enqueue_at(
+        &self,
+        iden: QueueIdentifier,
+        enq_job: EnqueuedJob,
+        time: SystemTime,
+    ) -> JobQueueResult<()> {
+        // Identical jobs scheduled more than once would otherwise collapse into a single member
+        let data = format!("{}:{}", Uuid::new_v4().hyphenated(), json!(enq_job));
+        let _: () = self.redis_con
+            .zadd(&self.scheduled_key(iden), data, unix_timestamp(time))
+            .map_err(|e| (e, ErrorOrigin::EnqueueAt))?;
+
+        Ok(())
+    }
+
+    /// Atomically move the due jobs from the sorted set into the queue.
+    fn enqueue_due_jobs(&self, iden: QueueIdentifier, now: SystemTime) -> JobQueueResult<usize> {
+        let count: usize = redis::Script::new(ENQUEUE_DUE_JOBS_SCRIPT)
+            .key(self.scheduled_key(iden))
+            .key(self.key(iden))
+            .arg(unix_timestamp(now))
+            .arg(self.push_back_command())
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::EnqueueDueJobs))?;
+
+        Ok(count)
     }
 
     /// Delete everything in the queue.
-    fn delete_all(&self) -> JobQueueResult<()> {
+    fn delete_all(&self, iden: QueueIdentifier) -> JobQueueResult<()> {
         let _: () = self.redis_con
-            .del(&self.key())
+            .del(vec![self.key(iden), self.scheduled_key(iden)])
             .map_err(|e| (e, ErrorOrigin::DeleteAll))?;
         Ok(())
     }
 
     /// The number of jobs in the queue.
-    fn size(&self) -> JobQueueResult<usize> {
+    fn size(&self, iden: QueueIdentifier) -> JobQueueResult<usize> {
         let size: usize = self.redis_con
-            .llen(&self.key())
+            .llen(&self.key(iden))
             .map_err(|e| (e, ErrorOrigin::Size))?;
         Ok(size)
     }
+
+    /// The number of jobs in the sorted set of scheduled jobs.
+    fn scheduled_size(&self, iden: QueueIdentifier) -> JobQueueResult<usize> {
+        let size: usize = self.redis_con
+            .zcard(&self.scheduled_key(iden))
+            .map_err(|e| (e, ErrorOrigin::ScheduledSize))?;
+        Ok(size)
+    }
+
+    /// All the jobs in the queue.
+    fn jobs(&self, iden: QueueIdentifier) -> JobQueueResult<Vec<EnqueuedJob>> {
+        let data: Vec<String> = self.redis_con
+            .lrange(&self.key(iden), 0, -1)
+            .map_err(|e| (e, ErrorOrigin::Jobs))?;
+
+        Ok(self.decode_jobs(data))
+    }
+
+    /// The range is counted from the end of the list jobs are popped from.
+    fn jobs_range(
+        &self,
+        iden: QueueIdentifier,
+        offset: usize,
+        limit: usize,
+    ) -> JobQueueResult<Vec<EnqueuedJob>> {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+
+        let (start, stop) = if self.reliable_fetch {
+            (-((offset + limit) as isize), -((offset + 1) as isize))
+        } else {
+            (offset as isize, (offset + limit - 1) as isize)
+        };
+        let data: Vec<String> = self.redis_con
+            .lrange(&self.key(iden), start, stop)
+            .map_err(|e| (e, ErrorOrigin::JobsRange))?;
+
+        Ok(self.decode_jobs(data))
+    }
+
+    /// Remove the occurrence of the job that would be dequeued first. Jobs are compared by the
+    /// JSON they were decoded from, if any, since jobs enqueued by earlier versions don't encode
+    /// to the same JSON again.
+    fn remove(&self, iden: QueueIdentifier, enq_job: &EnqueuedJob) -> JobQueueResult<bool> {
+        let removed: usize = self.redis_con
+            .lrem(&self.key(iden), self.remove_first_count(), enq_job.encode())
+            .map_err(|e| (e, ErrorOrigin::Remove))?;
+        Ok(removed > 0)
+    }
+
+    fn move_job(
+        &self,
+        from: QueueIdentifier,
+        enq_job: &EnqueuedJob,
+        to: QueueIdentifier,
+        moved_job: EnqueuedJob,
+    ) -> JobQueueResult<bool> {
+        let moved: bool = redis::Script::new(MOVE_JOB_SCRIPT)
+            .key(self.key(from))
+            .key(self.key(to))
+            .arg(enq_job.encode())
+            .arg(json!(moved_job).to_string())
+            .arg(self.remove_first_count())
+            .arg(self.push_back_command())
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::MoveJob))?;
+        Ok(moved)
+    }
+
+    fn poison(&self, poisoned_job: PoisonedJob) -> JobQueueResult<()> {
+        let data: String = json!(poisoned_job).to_string();
+        let _: () = self.redis_con
+            .lpush(&self.poison_key(), data)
+            .map_err(|e| (e, ErrorOrigin::Poison))?;
+        Ok(())
+    }
+
+    fn poisoned_jobs(&self) -> JobQueueResult<Vec<PoisonedJob>> {
+        let data: Vec<String> = self.redis_con
+            .lrange(&self.poison_key(), 0, -1)
+            .map_err(|e| (e, ErrorOrigin::PoisonedJobs))?;
+
+        data.iter()
+            .rev()
+            .map(|data| {
+                serde_json::from_str(data)
+                    .map_err(|e| JobQueueError::from((e, ErrorOrigin::PoisonedJobs)))
+            })
+            .collect()
+    }
+
+    fn delete_all_poisoned(&self) -> JobQueueResult<()> {
+        let _: () = self.redis_con
+            .del(&self.poison_key())
+            .map_err(|e| (e, ErrorOrigin::DeleteAllPoisoned))?;
+        Ok(())
+    }
+
+    fn set_job_status(
+        &self,
+        id: &JobId,
+        status: &JobStatus,
+        ttl: Duration,
+    ) -> JobQueueResult<()> {
+        let data: String = json!(status).to_string();
+        let ttl_in_seconds = ttl.as_secs().max(1) as usize;
+        let _: () = self.redis_con
+            .set_ex(&self.job_status_key(id), data, ttl_in_seconds)
+            .map_err(|e| (e, ErrorOrigin::SetJobStatus))?;
+        Ok(())
+    }
+
+    /// Store the statuses with a single round trip.
+    fn set_job_statuses(
+        &self,
+        ids: &[JobId],
+        status: &JobStatus,
+        ttl: Duration,
+    ) -> JobQueueResult<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let data: String = json!(status).to_string();
+        let ttl_in_seconds = ttl.as_secs().max(1) as usize;
+
+        let mut pipe = redis::pipe();
+        for id in ids {
+            pipe.set_ex(self.job_status_key(id), &data, ttl_in_seconds).ignore();
+        }
+        let _: () = pipe.query(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::SetJobStatuses))?;
+        Ok(())
+    }
+
+    fn lock_unique(&self, key: &str, id: &JobId, ttl: Duration) -> JobQueueResult<Option<JobId>> {
+        let ttl_in_millis = (ttl.as_secs() * 1_000 + u64::from(ttl.subsec_nanos() / 1_000_000))
+            .max(1);
+        let holder: Option<String> = redis::Script::new(LOCK_UNIQUE_SCRIPT)
+            .key(self.unique_key(key))
+            .arg(id.as_str())
+            .arg(ttl_in_millis)
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::LockUnique))?;
+        Ok(holder.map(JobId::from))
+    }
+
+    fn create_batch(
+        &self,
+        id: &BatchId,
+        job_ids: &[JobId],
+        callbacks: &[BatchCallback],
+    ) -> JobQueueResult<()> {
+        // The callbacks are stored with the key and payload the script pushes, since it can't
+        // encode jobs itself
+        let callbacks: Vec<Value> = callbacks
+            .iter()
+            .map(|callback| {
+                json!({
+                    "event": callback.event(),
+                    "key": self.key(callback.queue()),
+                    "job": callback.job().encode(),
+                })
+            })
+            .collect();
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset(self.batch_key(id), "total", job_ids.len())
+            .ignore()
+            .hset(self.batch_key(id), "pending", job_ids.len())
+            .ignore()
+            .hset(self.batch_key(id), "succeeded", 0)
+            .ignore()
+            .hset(self.batch_key(id), "failed", 0)
+            .ignore()
+            .hset(self.batch_key(id), "callbacks", json!(callbacks).to_string())
+            .ignore();
+        for chunk in job_ids.chunks(ENQUEUE_MANY_CHUNK_SIZE) {
+            let ids: Vec<&str> = chunk.iter().map(JobId::as_str).collect();
+            pipe.sadd(self.batch_pending_key(id), ids).ignore();
+        }
+        let _: () = pipe.query(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::CreateBatch))?;
+        Ok(())
+    }
+
+    fn finish_batch_job(
+        &self,
+        id: &BatchId,
+        job_id: &JobId,
+        succeeded: bool,
+        ttl: Duration,
+    ) -> JobQueueResult<Option<(BatchStatus, Vec<EnqueuedJob>)>> {
+        let field = if succeeded { "succeeded" } else { "failed" };
+        let result: Option<(usize, usize, usize, usize, Vec<String>)> =
+            redis::Script::new(FINISH_BATCH_JOB_SCRIPT)
+            .key(self.batch_key(id))
+            .key(self.batch_pending_key(id))
+            .arg(job_id.as_str())
+            .arg(field)
+            .arg(ttl.as_secs().max(1))
+            .arg(self.push_back_command())
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::FinishBatchJob))?;
+
+        let (total, pending, succeeded, failed, enqueued) = match result {
+            Some(result) => result,
+            None => return Ok(None),
+        };
+        let status = BatchStatus::from_counts(total, pending, succeeded, failed);
+        let callbacks = enqueued
+            .iter()
+            .map(|data| EnqueuedJob::decode(data))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| (e, ErrorOrigin::FinishBatchJob))?;
+        Ok(Some((status, callbacks)))
+    }
+
+    fn batch_status(&self, id: &BatchId) -> JobQueueResult<Option<BatchStatus>> {
+        let fields = ["total", "pending", "succeeded", "failed"];
+        let counts: (Option<usize>, Option<usize>, Option<usize>, Option<usize>) =
+            self.redis_con
+                .hget(self.batch_key(id), &fields[..])
+                .map_err(|e| (e, ErrorOrigin::BatchStatus))?;
+
+        match counts {
+            (Some(total), Some(pending), Some(succeeded), Some(failed)) => Ok(Some(
+                BatchStatus::from_counts(total, pending, succeeded, failed),
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    fn last_periodic_run(&self, key: &str) -> JobQueueResult<Option<SystemTime>> {
+        let millis: Option<u64> = self.redis_con
+            .get(self.periodic_key(key))
+            .map_err(|e| (e, ErrorOrigin::LastPeriodicRun))?;
+        Ok(millis.map(|millis| UNIX_EPOCH + Duration::from_millis(millis)))
+    }
+
+    fn claim_periodic_run(
+        &self,
+        key: &str,
+        previous: Option<SystemTime>,
+        run: SystemTime,
+    ) -> JobQueueResult<bool> {
+        let previous = previous.map_or_else(String::new, |time| unix_millis(time).to_string());
+        let claimed: bool = redis::Script::new(CLAIM_PERIODIC_RUN_SCRIPT)
+            .key(self.periodic_key(key))
+            .arg(previous)
+            .arg(unix_millis(run))
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::ClaimPeriodicRun))?;
+        Ok(claimed)
+    }
+
+    fn acquire_rate_limit(
+        &self,
+        key: &str,
+        limit: u32,
+        period: Duration,
+    ) -> JobQueueResult<Option<Duration>> {
+        let period_in_millis = (period.as_secs() * 1_000
+            + u64::from(period.subsec_nanos() / 1_000_000))
+            .max(1);
+        let wait_in_millis: u64 = redis::Script::new(ACQUIRE_RATE_LIMIT_SCRIPT)
+            .key(self.rate_limit_key(key))
+            .arg(limit)
+            .arg(period_in_millis)
+            .arg(Uuid::new_v4().hyphenated().to_string())
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::AcquireRateLimit))?;
+
+        if wait_in_millis == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Duration::from_millis(wait_in_millis)))
+        }
+    }
+
+    fn acquire_concurrency_slot(
+        &self,
+        key: &str,
+        id: &JobId,
+        limit: u32,
+        lease: Duration,
+    ) -> JobQueueResult<bool> {
+        let lease_in_millis = (lease.as_secs() * 1_000
+            + u64::from(lease.subsec_nanos() / 1_000_000))
+            .max(1);
+        let acquired: bool = redis::Script::new(ACQUIRE_CONCURRENCY_SLOT_SCRIPT)
+            .key(self.concurrency_key(key))
+            .arg(unix_millis(SystemTime::now()))
+            .arg(limit)
+            .arg(id.as_str())
+            .arg(lease_in_millis)
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::AcquireConcurrencySlot))?;
+        Ok(acquired)
+    }
+
+    fn release_concurrency_slot(&self, key: &str, id: &JobId) -> JobQueueResult<()> {
+        let _: () = self.redis_con
+            .zrem(self.concurrency_key(key), id.as_str())
+            .map_err(|e| (e, ErrorOrigin::ReleaseConcurrencySlot))?;
+        Ok(())
+    }
+
+    fn delete_batch(&self, id: &BatchId) -> JobQueueResult<()> {
+        let _: () = self.redis_con
+            .del(&[self.batch_key(id), self.batch_pending_key(id)][..])
+            .map_err(|e| (e, ErrorOrigin::DeleteBatch))?;
+        Ok(())
+    }
+
+    fn unlock_unique(&self, key: &str, id: &JobId) -> JobQueueResult<()> {
+        let _: () = redis::Script::new(UNLOCK_UNIQUE_SCRIPT)
+            .key(self.unique_key(key))
+            .arg(id.as_str())
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::UnlockUnique))?;
+        Ok(())
+    }
+
+    fn heartbeat(&self, info: &WorkerInfo, ttl: Duration) -> JobQueueResult<()> {
+        let data: String = json!(info).to_string();
+        let ttl_in_seconds = ttl.as_secs().max(1) as usize;
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(self.worker_key(info.id()), data, ttl_in_seconds)
+            .ignore()
+            .sadd(self.workers_key(), info.id())
+            .ignore()
+            .query(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::Heartbeat))?;
+        Ok(())
+    }
+
+    fn workers(&self) -> JobQueueResult<Vec<WorkerInfo>> {
+        let ids: Vec<String> = self.redis_con
+            .smembers(self.workers_key())
+            .map_err(|e| (e, ErrorOrigin::Workers))?;
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut pipe = redis::pipe();
+        for id in &ids {
+            pipe.get(self.worker_key(id));
+        }
+        let data: Vec<Option<String>> = pipe.query(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::Workers))?;
+
+        let mut workers = vec![];
+        for (id, data) in ids.iter().zip(data) {
+            match data {
+                Some(data) => match serde_json::from_str(&data) {
+                    Ok(info) => workers.push(info),
+                    // Such as a heartbeat from a newer version, which shouldn't hide the others
+                    Err(err) => warn!("Skipped unreadable heartbeat of worker {}\n{:?}", id, err),
+                },
+                // The heartbeat has expired
+                None => {
+                    let _: () = self.redis_con
+                        .srem(self.workers_key(), id.as_str())
+                        .map_err(|e| (e, ErrorOrigin::Workers))?;
+                }
+            }
+        }
+        Ok(workers)
+    }
+
+    fn remove_worker(&self, id: &str) -> JobQueueResult<()> {
+        let _: () = redis::pipe()
+            .atomic()
+            .del(self.worker_key(id))
+            .ignore()
+            .srem(self.workers_key(), id)
+            .ignore()
+            .query(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::RemoveWorker))?;
+        Ok(())
+    }
+
+    fn job_status(&self, id: &JobId) -> JobQueueResult<Option<JobStatus>> {
+        let data: Option<String> = self.redis_con
+            .get(&self.job_status_key(id))
+            .map_err(|e| (e, ErrorOrigin::JobStatus))?;
+
+        match data {
+            Some(data) => serde_json::from_str(&data)
+                .map(Some)
+                .map_err(|e| JobQueueError::from((e, ErrorOrigin::JobStatus))),
+            None => Ok(None),
+        }
+    }
+
+    fn swap_job_status(
+        &self,
+        id: &JobId,
+        previous: Option<&JobStatus>,
+        status: &JobStatus,
+        ttl: Duration,
+    ) -> JobQueueResult<bool> {
+        let previous = previous.map_or_else(String::new, |previous| json!(previous).to_string());
+        let swapped: i32 = redis::Script::new(SWAP_JOB_STATUS_SCRIPT)
+            .key(self.job_status_key(id))
+            .arg(previous)
+            .arg(json!(status).to_string())
+            .arg(ttl.as_secs().max(1))
+            .invoke(&*self.redis_con)
+            .map_err(|e| (e, ErrorOrigin::SwapJobStatus))?;
+        Ok(swapped == 1)
+    }
 }
 
 impl Debug for RedisQueue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "RedisQueue {{ key: {:?}, redis_url: {:?} }}",
-            self.key, self.redis_url
+            "RedisQueue {{ id: {:?}, namespace: {:?}, redis_url: {:?} }}",
+            self.id, self.namespace, self.redis_url
         )
     }
 }
@@ -1,10 +1,20 @@
-use config::Config;
+use config::{Config, RetryPolicy};
 use connection::*;
+use error::{JobPanicked, JobTimedOut};
+use heartbeat::{CurrentJob, WorkerInfo};
 use job::*;
-use queue_adapters::{JobQueue, NoJobDequeued, QueueIdentifier, RetryCount};
+use metrics::MetricsSink;
+use queue_adapters::{EnqueuedJob, JobQueue, NoJobDequeued, PoisonReason, PoisonedJob,
+                     QueueIdentifier};
 use serde_json;
+use signal_hook::{self, SIGINT, SIGTERM};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::*;
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
 /// Boot the worker.
 ///
@@ -13,17 +23,53 @@ use std::thread::{self, JoinHandle};
 ///
 /// This will spawn the numbers of workers set by
 /// [`config.worker_count`](../config/struct.Config.html#structfield.worker_count) plus one for
-/// handling retries.
+/// the retry queue, which holds jobs retried by earlier versions of Robin. An additional thread
+/// is spawned for moving scheduled jobs onto their queues once they're due, and another for
+/// recording heartbeats.
+///
+/// The workers keep performing jobs until the process receives `SIGTERM` or `SIGINT`. The
+/// workers then stop dequeuing new jobs and are given
+/// [`config.shutdown_timeout`](../config/struct.Config.html#structfield.shutdown_timeout) to finish
+/// the jobs they're performing, before this function returns.
+///
+/// On `SIGTSTP` the workers stop dequeuing new jobs but keep running until they're told to shut
+/// down. Send this before stopping the process to make sure no job is interrupted.
 ///
 /// Make sure the config you're using here is the same config you use to establish the connection
 /// in [`robin_establish_connection!`](../macro.robin_establish_connection.html).
-pub fn boot<Q, T, K>(config: &Config, queue_config: K, lookup_job: T)
+pub fn boot<Q, T, K>(config: &Config, queue_config: K, lookup_job: T) -> Shutdown
 where
     K: 'static + Clone + Send,
-    Q: JobQueue<Config = K>,
+    Q: 'static + JobQueue<Config = K>,
     T: 'static + LookupJob<Q> + Send + Clone,
 {
-    spawn_workers(config, queue_config, lookup_job).job_loop()
+    let terminate = Arc::new(AtomicBool::new(false));
+    let quiet = Arc::new(AtomicBool::new(false));
+
+    for signal in &[SIGTERM, SIGINT] {
+        signal_hook::flag::register(*signal, Arc::clone(&terminate))
+            .expect("failed to register signal handler");
+    }
+    signal_hook::flag::register(libc::SIGTSTP, Arc::clone(&quiet))
+        .expect("failed to register signal handler");
+
+    let manager = spawn_workers(config, queue_config, lookup_job);
+    let mut quieted = false;
+
+    loop {
+        thread::sleep(Duration::from_millis(100));
+
+        if terminate.load(Ordering::SeqCst) {
+            info!("Shutting down workers");
+            return manager.die_within(config.shutdown_timeout);
+        }
+
+        if !quieted && quiet.load(Ordering::SeqCst) {
+            info!("Workers will stop dequeuing new jobs");
+            manager.quiet();
+            quieted = true;
+        }
+    }
 }
 
 /// Spawn the workers and return the [`WorkerManager`](struct.WorkerManager) which enables
@@ -36,52 +82,172 @@ where
 pub fn spawn_workers<Q, T, K>(config: &Config, queue_config: K, lookup_job: T) -> WorkerManager
 where
     K: 'static + Clone + Send,
-    Q: JobQueue<Config = K>,
+    Q: 'static + JobQueue<Config = K>,
     T: 'static + LookupJob<Q> + Send + Clone,
 {
+    recover_orphaned_jobs(config, &queue_config, &lookup_job);
+
+    let (finished_sender, finished) = channel();
     let mut channel: MultiplexChannel<WorkerMessage> = MultiplexChannel::new();
+    let tracker = Tracker {
+        stopping: Arc::new(AtomicBool::new(false)),
+        busy_workers: Arc::new(AtomicUsize::new(0)),
+        finished: finished_sender,
+        subscribers: Subscribers::default(),
+        current_jobs: Arc::new(Mutex::new(BTreeMap::new())),
+    };
 
-    let mut handles: Vec<JoinHandle<()>> = config
+    let mut worker_queues: Vec<Vec<(QueueIdentifier, u32)>> = config
         .worker_count
         .times()
-        .map(|_| {
+        .map(|_| config.weighted_queues())
+        .collect();
+    for settings in &config.queues {
+        for _ in settings.workers.times() {
+            worker_queues.push(vec![(settings.queue, 1)]);
+        }
+    }
+    worker_queues.push(vec![(QueueIdentifier::Retry, 1)]);
+
+    let mut served_queues = vec![];
+    for &(iden, _) in worker_queues.iter().flat_map(|queues| queues) {
+        if !served_queues.contains(&iden) {
+            served_queues.push(iden);
+        }
+    }
+    let worker_info = WorkerInfo::new(worker_queues.len(), &served_queues);
+
+    let mut handles: Vec<JoinHandle<()>> = worker_queues
+        .into_iter()
+        .enumerate()
+        .map(|(thread_number, queues)| {
             spawn_worker(
                 channel.new_receiver(),
+                thread_number,
                 &config,
                 &lookup_job,
-                QueueIdentifier::Main,
+                queues,
                 queue_config.clone(),
+                tracker.clone(),
             )
         })
         .collect();
 
-    handles.push(spawn_worker(
+    handles.push(spawn_scheduler(
         channel.new_receiver(),
         &config,
         &lookup_job,
-        QueueIdentifier::Retry,
         queue_config.clone(),
+        tracker.clone(),
     ));
 
-    WorkerManager { handles, channel }
+    handles.push(spawn_heartbeat(
+        channel.new_receiver(),
+        &config,
+        &lookup_job,
+        queue_config.clone(),
+        tracker.clone(),
+        worker_info,
+    ));
+
+    WorkerManager {
+        handles,
+        channel,
+        finished,
+        stopping: tracker.stopping,
+        busy_workers: tracker.busy_workers,
+        subscribers: tracker.subscribers,
+    }
+}
+
+fn recover_orphaned_jobs<T, Q, K>(config: &Config, queue_config: &K, lookup_job: &T)
+where
+    K: 'static + Clone + Send,
+    Q: 'static + JobQueue<Config = K>,
+    T: 'static + LookupJob<Q> + Send + Clone,
+{
+    let con = establish(config.clone(), queue_config.clone(), lookup_job.clone())
+        .expect("failed to establish connection");
+
+    match con.recover_orphaned_jobs() {
+        Ok(0) => {}
+        Ok(count) => info!("Recovered {} jobs from crashed workers", count),
+        Err(err) => error!("Failed to recover jobs from crashed workers\n{:?}", err),
+    }
 }
 
 fn spawn_worker<T, Q, K>(
     receiver: Receiver<WorkerMessage>,
+    thread_number: usize,
     config: &Config,
     lookup_job: &T,
-    queue_iden: QueueIdentifier,
+    queues: Vec<(QueueIdentifier, u32)>,
     queue_config: K,
+    tracker: Tracker,
 ) -> JoinHandle<()>
 where
     K: 'static + Clone + Send,
-    Q: JobQueue<Config = K>,
+    Q: 'static + JobQueue<Config = K>,
+    T: 'static + LookupJob<Q> + Send + Clone,
+{
+    let config = config.clone();
+    let queue_config = queue_config.clone();
+    let lookup_job = lookup_job.clone();
+    thread::spawn(move || {
+        let _finished = tracker.finished_on_drop();
+        worker_loop(
+            receiver,
+            thread_number,
+            config,
+            lookup_job,
+            queues,
+            queue_config,
+            &tracker,
+        )
+    })
+}
+
+fn spawn_scheduler<T, Q, K>(
+    receiver: Receiver<WorkerMessage>,
+    config: &Config,
+    lookup_job: &T,
+    queue_config: K,
+    tracker: Tracker,
+) -> JoinHandle<()>
+where
+    K: 'static + Clone + Send,
+    Q: 'static + JobQueue<Config = K>,
     T: 'static + LookupJob<Q> + Send + Clone,
 {
     let config = config.clone();
     let queue_config = queue_config.clone();
     let lookup_job = lookup_job.clone();
-    thread::spawn(move || worker_loop(receiver, config, lookup_job, queue_iden, queue_config))
+    thread::spawn(move || {
+        let _finished = tracker.finished_on_drop();
+        scheduler_loop(receiver, config, lookup_job, queue_config)
+    })
+}
+
+fn spawn_heartbeat<T, Q, K>(
+    receiver: Receiver<WorkerMessage>,
+    config: &Config,
+    lookup_job: &T,
+    queue_config: K,
+    tracker: Tracker,
+    info: WorkerInfo,
+) -> JoinHandle<()>
+where
+    K: 'static + Clone + Send,
+    Q: 'static + JobQueue<Config = K>,
+    T: 'static + LookupJob<Q> + Send + Clone,
+{
+    let config = config.clone();
+    let queue_config = queue_config.clone();
+    let lookup_job = lookup_job.clone();
+    thread::spawn(move || {
+        let _finished = tracker.finished_on_drop();
+        heartbeat_loop(receiver, config, lookup_job, queue_config, &tracker, info)
+    })
 }
 
 /// Struct the allows you to communicate with the running workers.
@@ -89,9 +255,56 @@ where
 pub struct WorkerManager {
     handles: Vec<JoinHandle<()>>,
     channel: MultiplexChannel<WorkerMessage>,
+    finished: Receiver<()>,
+    stopping: Arc<AtomicBool>,
+    busy_workers: Arc<AtomicUsize>,
+    subscribers: Subscribers,
 }
 
 impl WorkerManager {
+    /// Receive the [`WorkerEvent`](enum.WorkerEvent.html)s of all the workers, from now on.
+    ///
+    /// Events that happened before subscribing aren't received, so enqueue the jobs you want to
+    /// follow after subscribing. The receiver stops receiving once all the workers have shut
+    /// down.
+    ///
+    /// ```rust
+    /// # #[macro_use]
+    /// # extern crate robin;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// # use robin::prelude::*;
+    /// # use robin::memory_queue::*;
+    /// use robin::worker::WorkerEvent;
+    ///
+    /// jobs! {
+    ///     SendEmail(String),
+    /// }
+    /// #
+    /// # impl SendEmail {
+    /// #     fn perform<Q>(_args: String, _con: &Connection<Q>) -> JobResult { Ok(()) }
+    /// # }
+    ///
+    /// # fn main() {
+    /// # let config = Config::default();
+    /// # let queue_config = MemoryQueueConfig::default();
+    /// # let con = robin_establish_connection!(MemoryQueue, config, queue_config).unwrap();
+    /// let manager = spawn_workers::<MemoryQueue, _, _>(&config, queue_config, __robin_lookup_job);
+    /// let events = manager.subscribe();
+    ///
+    /// let id = SendEmail::perform_later(&"alice@example.com".to_string(), &con).unwrap();
+    /// manager.perform_all_jobs_and_die();
+    ///
+    /// assert!(events.iter().any(|event| match event {
+    ///     WorkerEvent::JobSucceeded { id: ref succeeded, .. } => *succeeded == id,
+    ///     _ => false,
+    /// }));
+    /// # }
+    /// ```
+    pub fn subscribe(&self) -> Receiver<WorkerEvent> {
+        self.subscribers.subscribe()
+    }
+
     /// Kill the workers once there are no more jobs left to perform.
     pub fn perform_all_jobs_and_die(self) {
         self.channel.send(WorkerMessage::PerformJobsAndDie);
@@ -100,126 +313,846 @@ impl WorkerManager {
 
     /// Kill the workers once they've performed the job they're currently working on.
     pub fn die(self) {
+        self.stopping.store(true, Ordering::SeqCst);
         self.channel.send(WorkerMessage::Die);
         self.join_threads();
     }
 
-    fn job_loop(self) {
-        self.join_threads()
+    /// Kill the workers once they've performed the job they're currently working on, but wait
+    /// at most `timeout` for them to finish.
+    ///
+    /// Workers that are still running when the timeout is hit are left behind, and will be
+    /// stopped when the process exits.
+    pub fn die_within(self, timeout: Duration) -> Shutdown {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.channel.send(WorkerMessage::Die);
+
+        let deadline = Instant::now() + timeout;
+        let mut running = self.handles.len();
+
+        while running > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+
+            match self.finished.recv_timeout(deadline - now) {
+                Ok(()) => running -= 1,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => running = 0,
+            }
+        }
+
+        if running == 0 {
+            self.join_threads();
+            Shutdown::Graceful
+        } else if self.busy_workers.load(Ordering::SeqCst) == 0 {
+            // The workers still running are blocked waiting for a job to be enqueued
+            Shutdown::Graceful
+        } else {
+            warn!("Shutdown timeout hit while jobs were still being performed");
+            Shutdown::TimedOut
+        }
+    }
+
+    /// Make the workers stop dequeuing new jobs. They'll keep running until they're told to die.
+    ///
+    /// Jobs that workers waiting for a job dequeue after this are put back into their queue.
+    pub fn quiet(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.channel.send(WorkerMessage::Quiet);
     }
 
     fn join_threads(self) {
-        self.handles.into_iter().for_each(|h| h.join().unwrap());
+        for handle in self.handles {
+            if handle.join().is_err() {
+                error!("A worker thread panicked");
+            }
+        }
+    }
+}
+
+/// How the workers were shut down.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Shutdown {
+    /// No jobs were being performed when the workers were shut down.
+    Graceful,
+
+    /// Some workers were still performing jobs when the shutdown timeout was hit.
+    TimedOut,
+}
+
+impl Shutdown {
+    /// The status code the process should exit with. `0` for a graceful shutdown and `1`
+    /// otherwise.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Shutdown::Graceful => 0,
+            Shutdown::TimedOut => 1,
+        }
     }
 }
 
+/// Something that happened in one of the workers.
+///
+/// Subscribe to the events with
+/// [`WorkerManager::subscribe`](struct.WorkerManager.html#method.subscribe). `attempt` is 1 the
+/// first time a job is performed, and goes up each time it's retried.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkerEvent {
+    /// A worker started performing a job.
+    JobStarted {
+        /// The name of the job
+        name: String,
+        /// The ID of the job
+        id: JobId,
+        /// The number of the attempt
+        attempt: u32,
+    },
+
+    /// A job succeeded.
+    JobSucceeded {
+        /// The name of the job
+        name: String,
+        /// The ID of the job
+        id: JobId,
+        /// The number of the attempt
+        attempt: u32,
+        /// How long performing the job took
+        duration: Duration,
+    },
+
+    /// A job failed. It's followed by `JobRetried` or `JobDead`.
+    JobFailed {
+        /// The name of the job
+        name: String,
+        /// The ID of the job
+        id: JobId,
+        /// The number of the attempt
+        attempt: u32,
+        /// How long performing the job took
+        duration: Duration,
+        /// The error the job failed with
+        error: String,
+    },
+
+    /// A failed job was put back into its queue, to be retried once its backoff has passed.
+    JobRetried {
+        /// The name of the job
+        name: String,
+        /// The ID of the job
+        id: JobId,
+        /// The number of the attempt that failed
+        attempt: u32,
+        /// How long performing the job took
+        duration: Duration,
+    },
+
+    /// A failed job was moved to the dead queue.
+    JobDead {
+        /// The name of the job
+        name: String,
+        /// The ID of the job
+        id: JobId,
+        /// The number of the attempt that failed
+        attempt: u32,
+        /// How long performing the job took
+        duration: Duration,
+    },
+
+    /// A worker waited for a job but its queues were empty.
+    WorkerIdle,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum WorkerMessage {
     Die,
     PerformJobsAndDie,
+    Quiet,
+}
+
+/// Book keeping shared between the worker threads and the `WorkerManager`.
+#[derive(Clone)]
+struct Tracker {
+    /// Set once the workers have been told to stop dequeuing jobs.
+    stopping: Arc<AtomicBool>,
+    busy_workers: Arc<AtomicUsize>,
+    finished: Sender<()>,
+    subscribers: Subscribers,
+    current_jobs: Arc<Mutex<BTreeMap<usize, CurrentJob>>>,
+}
+
+impl Tracker {
+    /// Signals that the thread has finished when the returned value is dropped, even if the
+    /// thread panics.
+    fn finished_on_drop(&self) -> FinishedOnDrop {
+        FinishedOnDrop(self.finished.clone())
+    }
+
+    /// Counts the worker as busy with `enq_job` until the returned value is dropped.
+    fn busy(&self, thread: usize, iden: QueueIdentifier, enq_job: &EnqueuedJob) -> BusyWorker {
+        self.busy_workers.fetch_add(1, Ordering::SeqCst);
+        self.current_jobs
+            .lock()
+            .expect("current jobs lock poisoned")
+            .insert(thread, CurrentJob::new(thread, iden, enq_job));
+        BusyWorker {
+            thread,
+            busy_workers: Arc::clone(&self.busy_workers),
+            current_jobs: Arc::clone(&self.current_jobs),
+        }
+    }
+
+    /// `true` once the workers have been told to stop dequeuing jobs.
+    fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+
+    /// The jobs the workers are performing right now.
+    fn current_jobs(&self) -> Vec<CurrentJob> {
+        self.current_jobs
+            .lock()
+            .expect("current jobs lock poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+/// The channels `WorkerEvent`s are sent to.
+#[derive(Clone, Default)]
+struct Subscribers(Arc<Mutex<Vec<Sender<WorkerEvent>>>>);
+
+impl Subscribers {
+    fn subscribe(&self) -> Receiver<WorkerEvent> {
+        let (sender, receiver) = channel();
+        self.0.lock().expect("subscribers lock poisoned").push(sender);
+        receiver
+    }
+
+    /// Send the event to all subscribers. The event is only created if there are any.
+    fn publish<F>(&self, event: F)
+    where
+        F: FnOnce() -> WorkerEvent,
+    {
+        let mut senders = self.0.lock().expect("subscribers lock poisoned");
+        if senders.is_empty() {
+            return;
+        }
+
+        let event = event();
+        senders.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+struct FinishedOnDrop(Sender<()>);
+
+impl Drop for FinishedOnDrop {
+    fn drop(&mut self) {
+        self.0.send(()).ok();
+    }
+}
+
+struct BusyWorker {
+    thread: usize,
+    busy_workers: Arc<AtomicUsize>,
+    current_jobs: Arc<Mutex<BTreeMap<usize, CurrentJob>>>,
+}
+
+impl Drop for BusyWorker {
+    fn drop(&mut self) {
+        if let Ok(mut current_jobs) = self.current_jobs.lock() {
+            current_jobs.remove(&self.thread);
+        }
+        self.busy_workers.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 fn worker_loop<Q, T, K>(
     receiver: Receiver<WorkerMessage>,
+    thread_number: usize,
     config: Config,
     lookup_job: T,
-    queue_iden: QueueIdentifier,
+    queues: Vec<(QueueIdentifier, u32)>,
     queue_config: K,
+    tracker: &Tracker,
 ) where
-    Q: JobQueue<Config = K>,
-    T: 'static + LookupJob<Q>,
+    K: 'static + Clone + Send,
+    Q: 'static + JobQueue<Config = K>,
+    T: 'static + LookupJob<Q> + Send + Clone,
 {
+    let fetch_strategy = config.fetch_strategy;
+    let mut executor = {
+        let config = config.clone();
+        let queue_config = queue_config.clone();
+        let lookup_job = lookup_job.clone();
+        Executor::new(move || {
+            spawn_executor(config.clone(), queue_config.clone(), lookup_job.clone())
+        })
+    };
     let con = establish(config, queue_config, lookup_job).expect("failed to establish connection");
     let mut received_perform_jobs_and_die = false;
+    let mut quiet = false;
 
     loop {
-        let job = con.dequeue_from(queue_iden);
-        let output = perform_job(job, &con);
-
-        match output {
-            PerformJobOutput::JobPerformed => {}
-            PerformJobOutput::JobRetried => {}
-            PerformJobOutput::NoJobPerformed(reason) => match reason {
-                NoJobPerformedReason::HitTimeout => if received_perform_jobs_and_die {
-                    break;
-                },
-                NoJobPerformedReason::RetryLimitReached => {
-                    debug!("retry limit reached");
+        if tracker.is_stopping() {
+            quiet = true;
+        }
+
+        if !quiet {
+            let job = con.dequeue_from(&fetch_strategy.order(&queues));
+
+            // The workers were told to stop while waiting for a job
+            if tracker.is_stopping() {
+                if let Ok((iden, _, enq_job)) = job {
+                    put_back(&con, iden, enq_job);
+                    quiet = true;
+                    continue;
                 }
-            },
+            }
+
+            let dequeued_from = dequeued_from(&job);
+            let output = {
+                let _busy = job.as_ref()
+                    .ok()
+                    .map(|&(iden, _, ref enq_job)| tracker.busy(thread_number, iden, enq_job));
+                perform_job(job, &con, &mut executor, &tracker.subscribers)
+            };
+
+            match output {
+                PerformJobOutput::JobPerformed
+                | PerformJobOutput::JobRetried
+                | PerformJobOutput::JobDead
+                | PerformJobOutput::JobPoisoned
+                | PerformJobOutput::JobCancelled
+                | PerformJobOutput::JobThrottled => if let Some(iden) = dequeued_from {
+                    if let Err(err) = con.ack(iden) {
+                        error!("Failed to acknowledge job\n{:?}", err);
+                    }
+                },
+                // Putting the job back acknowledged it
+                PerformJobOutput::JobPutBack => {}
+                PerformJobOutput::NoJobPerformed(reason) => match reason {
+                    NoJobPerformedReason::HitTimeout => {
+                        tracker.subscribers.publish(|| WorkerEvent::WorkerIdle);
+                        if received_perform_jobs_and_die {
+                            break;
+                        }
+                    }
+                    // Give the queue a moment to recover instead of hammering it
+                    NoJobPerformedReason::DequeueFailed => {
+                        thread::sleep(con.config().scheduled_poll_interval)
+                    }
+                },
+            }
         }
 
-        if let Ok(msg) = receiver.try_recv() {
-            match msg {
-                WorkerMessage::Die => break,
-                WorkerMessage::PerformJobsAndDie => received_perform_jobs_and_die = true,
+        let msg = if quiet {
+            match receiver.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            }
+        } else {
+            match receiver.try_recv() {
+                Ok(msg) => msg,
+                Err(_) => continue,
             }
+        };
+
+        match msg {
+            WorkerMessage::Die => break,
+            WorkerMessage::PerformJobsAndDie => if quiet {
+                break;
+            } else {
+                received_perform_jobs_and_die = true;
+            },
+            WorkerMessage::Quiet => quiet = true,
+        }
+    }
+}
+
+/// Put a job that was dequeued after the workers were told to stop back into its queue, so
+/// it isn't interrupted when the process exits.
+fn put_back<Q: JobQueue>(con: &Connection<Q>, iden: QueueIdentifier, enq_job: EnqueuedJob) {
+    if let Err(err) = con.put_back(iden, enq_job) {
+        error!("Failed to put job back into its queue\n{:?}", err);
+    }
+}
+
+fn scheduler_loop<Q, T, K>(
+    receiver: Receiver<WorkerMessage>,
+    config: Config,
+    lookup_job: T,
+    queue_config: K,
+) where
+    Q: JobQueue<Config = K>,
+    T: 'static + LookupJob<Q>,
+{
+    let con = establish(config, queue_config, lookup_job).expect("failed to establish connection");
+
+    loop {
+        match con.enqueue_due_jobs() {
+            Ok(0) => {}
+            Ok(count) => debug!("Enqueued {} scheduled jobs", count),
+            Err(err) => error!("Failed to enqueue scheduled jobs\n{:?}", err),
+        }
+
+        match con.enqueue_periodic_jobs(SystemTime::now()) {
+            Ok(0) => {}
+            Ok(count) => debug!("Enqueued {} periodic jobs", count),
+            Err(err) => error!("Failed to enqueue periodic jobs\n{:?}", err),
+        }
+
+        report_queue_depths(&con);
+
+        match receiver.recv_timeout(con.config().scheduled_poll_interval) {
+            Ok(WorkerMessage::Quiet) | Err(RecvTimeoutError::Timeout) => {}
+            Ok(_) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+fn heartbeat_loop<Q, T, K>(
+    receiver: Receiver<WorkerMessage>,
+    config: Config,
+    lookup_job: T,
+    queue_config: K,
+    tracker: &Tracker,
+    info: WorkerInfo,
+) where
+    Q: JobQueue<Config = K>,
+    T: 'static + LookupJob<Q>,
+{
+    let con = establish(config, queue_config, lookup_job).expect("failed to establish connection");
+    let mut info = info;
+
+    loop {
+        info = info.beat(tracker.current_jobs());
+        if let Err(err) = con.heartbeat(&info) {
+            error!("Failed to record heartbeat\n{:?}", err);
+        }
+
+        match receiver.recv_timeout(con.config().heartbeat_interval) {
+            Ok(WorkerMessage::Quiet) | Err(RecvTimeoutError::Timeout) => {}
+            Ok(_) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    if let Err(err) = con.remove_worker(&info) {
+        error!("Failed to remove heartbeat\n{:?}", err);
+    }
+}
+
+fn report_queue_depths<Q: JobQueue>(con: &Connection<Q>) {
+    let metrics = &con.config().metrics;
+    if metrics.is_empty() {
+        return;
+    }
+
+    for iden in con.config().queue_identifiers() {
+        match con.queue_size(iden) {
+            Ok(size) => metrics.queue_depth(iden, size),
+            Err(err) => error!("Failed to get the size of the {} queue\n{:?}", iden.name(), err),
         }
     }
 }
 
-type DequeuedJob<Q> = Result<(Box<Job<Q> + Send + 'static>, String, RetryCount), NoJobDequeued>;
+type DequeuedJob<Q> =
+    Result<(QueueIdentifier, Box<Job<Q> + Send + 'static>, EnqueuedJob), NoJobDequeued>;
+
+/// The queue that needs to be acknowledged once the dequeued job has been dealt with.
+fn dequeued_from<Q>(job: &DequeuedJob<Q>) -> Option<QueueIdentifier> {
+    match *job {
+        Ok((iden, _, _))
+        | Err(NoJobDequeued::BecauseUnknownJob(iden, _))
+        | Err(NoJobDequeued::BecauseMalformedPayload(iden, _)) => Some(iden),
+        Err(NoJobDequeued::BecauseTimeout) | Err(NoJobDequeued::BecauseError(_)) => None,
+    }
+}
 
 #[derive(Debug)]
 enum PerformJobOutput {
     JobPerformed,
     JobRetried,
+    JobDead,
+    JobPoisoned,
+    JobCancelled,
+    JobThrottled,
+    JobPutBack,
     NoJobPerformed(NoJobPerformedReason),
 }
 
 #[derive(Debug)]
 enum NoJobPerformedReason {
     HitTimeout,
-    RetryLimitReached,
+    DequeueFailed,
 }
 
-fn perform_job<Q>(job: DequeuedJob<Q>, con: &Connection<Q>) -> PerformJobOutput
+fn perform_job<Q>(
+    job: DequeuedJob<Q>,
+    con: &Connection<Q>,
+    executor: &mut Executor<Q>,
+    subscribers: &Subscribers,
+) -> PerformJobOutput
 where
     Q: JobQueue,
 {
     match job {
-        Ok((job, args, retry_count)) => perform_or_retry(con, job, args, retry_count),
+        Ok((iden, job, enq_job)) => {
+            perform_or_retry(con, executor, subscribers, iden, job, enq_job)
+        }
 
         Err(NoJobDequeued::BecauseTimeout) => {
             PerformJobOutput::NoJobPerformed(NoJobPerformedReason::HitTimeout)
         }
 
         Err(NoJobDequeued::BecauseError(err)) => {
-            panic!(format!("Failed to dequeue job with error\n{:?}", err))
+            error!("Failed to dequeue job with error\n{:?}", err);
+            PerformJobOutput::NoJobPerformed(NoJobPerformedReason::DequeueFailed)
+        }
+
+        Err(NoJobDequeued::BecauseUnknownJob(_, enq_job)) => {
+            if let Err(err) = con.quarantine(enq_job) {
+                error!("Failed to move unknown job to the dead queue\n{:?}", err);
+            }
+            PerformJobOutput::JobDead
+        }
+
+        Err(NoJobDequeued::BecauseMalformedPayload(_, poisoned_job)) => {
+            poison(con, poisoned_job)
         }
+    }
+}
 
-        Err(NoJobDequeued::BecauseUnknownJob(name)) => {
-            panic!(format!("Received unknown job from the queue\n{:?}", name.0))
+/// Postpone a job that hit its rate limit or concurrency limit for `wait`. If that fails the job
+/// is put back into its queue instead, so it isn't lost.
+fn throttle<Q: JobQueue>(
+    con: &Connection<Q>,
+    iden: QueueIdentifier,
+    enq_job: EnqueuedJob,
+    wait: Duration,
+) -> PerformJobOutput {
+    match con.throttle(iden, enq_job.clone(), wait) {
+        Ok(()) => PerformJobOutput::JobThrottled,
+        Err(err) => {
+            error!(
+                "Failed to postpone job {}, putting it back into its queue\n{:?}",
+                enq_job.id(),
+                err
+            );
+            put_back(con, iden, enq_job);
+            PerformJobOutput::JobPutBack
         }
     }
 }
 
+/// Record that a job in a batch finished. The job has already been dealt with by then, so an
+/// error is logged rather than failing it.
+fn finish_batch_job<Q: JobQueue>(con: &Connection<Q>, enq_job: &EnqueuedJob, succeeded: bool) {
+    if let Err(err) = con.batch_job_finished(enq_job, succeeded) {
+        error!(
+            "Failed to record that job {} finished in its batch\n{:?}",
+            enq_job.id(),
+            err
+        );
+    }
+}
+
+fn poison<Q: JobQueue>(con: &Connection<Q>, poisoned_job: PoisonedJob) -> PerformJobOutput {
+    if let Err(err) = con.poison(poisoned_job) {
+        error!("Failed to move malformed payload to the poison queue\n{:?}", err);
+    }
+    PerformJobOutput::JobPoisoned
+}
+
 fn perform_or_retry<Q: JobQueue>(
     con: &Connection<Q>,
+    executor: &mut Executor<Q>,
+    subscribers: &Subscribers,
+    iden: QueueIdentifier,
     job: Box<Job<Q> + Send>,
-    args: String,
-    retry_count: RetryCount,
+    enq_job: EnqueuedJob,
 ) -> PerformJobOutput {
-    let retry_count = retry_count.increment();
-
-    if retry_count.limit_reached(con.config()) {
-        PerformJobOutput::NoJobPerformed(NoJobPerformedReason::RetryLimitReached)
-    } else {
-        // TODO: Handle this error
-        let args = serde_json::from_str(&args).expect("TODO");
-        let job_result = job.perform(&args, &con);
-
-        match job_result {
-            Ok(()) => PerformJobOutput::JobPerformed,
-            Err(_) => {
-                con.retry(job.name(), &args, retry_count)
-                    .expect("Failed to enqueue job into retry queue");
+    let name = job.name();
+    let options = job.options();
+    let policy = options
+        .retry
+        .unwrap_or_else(|| con.config().retry_policy());
+
+    let args: Args = match serde_json::from_str(enq_job.args()) {
+        Ok(args) => args,
+        Err(err) => {
+            let payload = json!(enq_job).to_string();
+            let poisoned_job =
+                PoisonedJob::new(&payload, PoisonReason::InvalidArgs, &err.to_string());
+            con.release_unique(&enq_job);
+            finish_batch_job(con, &enq_job, false);
+            return poison(con, poisoned_job);
+        }
+    };
+
+    // The concurrency slot is taken first, since it can be given back if the rate limit is hit.
+    // A rate limit token can't be.
+    if let Some(ref concurrency) = options.concurrency {
+        if !con.acquire_concurrency_slot(&name, concurrency, &enq_job) {
+            let wait = con.config().scheduled_poll_interval;
+            return throttle(con, iden, enq_job, wait);
+        }
+    }
+
+    if let Some(rate_limit) = options.rate_limit {
+        if let Some(wait) = con.acquire_rate_limit(&name, &rate_limit) {
+            if let Some(ref concurrency) = options.concurrency {
+                con.release_concurrency_slot(&name, concurrency, &enq_job);
+            }
+            return throttle(con, iden, enq_job, wait);
+        }
+    }
+
+    // Checked last and atomically with recording the new status, so a job cancelled while
+    // waiting for a rate limit or concurrency slot is never performed
+    if !con.job_started(&enq_job) {
+        debug!("Skipped cancelled job {}", enq_job.id());
+        if let Some(ref concurrency) = options.concurrency {
+            con.release_concurrency_slot(&name, concurrency, &enq_job);
+        }
+        con.release_unique(&enq_job);
+        finish_batch_job(con, &enq_job, false);
+        return PerformJobOutput::JobCancelled;
+    }
+    let attempt = enq_job.retry_count().attempt();
+    subscribers.publish(|| WorkerEvent::JobStarted {
+        name: name.0.clone(),
+        id: enq_job.id().clone(),
+        attempt,
+    });
+    let started = Instant::now();
+    let (outcome, still_running) = match options.timeout.or(con.config().job_timeout) {
+        Some(timeout) => executor.perform(job, &enq_job, args, policy, timeout),
+        None => (perform(&*job, &enq_job, &args, con, &policy), false),
+    };
+    let duration = started.elapsed();
+    con.config().metrics.job_processed(&name.0, duration);
+
+    // A job that timed out holds on to its slot until it finishes, see `spawn_executor`
+    if let (Some(ref concurrency), false) = (options.concurrency, still_running) {
+        con.release_concurrency_slot(&name, concurrency, &enq_job);
+    }
+
+    // The next job in a chain is enqueued before the job is recorded as succeeded, so the job
+    // is retried if that fails
+    let outcome = match outcome {
+        Outcome::Succeeded => match con.enqueue_continuation(&enq_job) {
+            Ok(()) => Outcome::Succeeded,
+            Err(err) => Outcome::Failed {
+                error: format!("Failed to enqueue the next job in the chain: {}", err),
+                retryable: true,
+            },
+        },
+        outcome => outcome,
+    };
+
+    match outcome {
+        Outcome::Succeeded => {
+            // Published before the batch callbacks are enqueued, so subscribers see their events
+            // after this one. Events of the next job in a chain may come first.
+            subscribers.publish(|| WorkerEvent::JobSucceeded {
+                name: name.0.clone(),
+                id: enq_job.id().clone(),
+                attempt,
+                duration,
+            });
+            con.job_succeeded(&enq_job);
+            con.release_unique(&enq_job);
+            finish_batch_job(con, &enq_job, true);
+            PerformJobOutput::JobPerformed
+        }
+        Outcome::Failed { error, retryable } => {
+            con.config().metrics.job_failed(&name.0);
+            subscribers.publish(|| WorkerEvent::JobFailed {
+                name: name.0.clone(),
+                id: enq_job.id().clone(),
+                attempt,
+                duration,
+                error: error.clone(),
+            });
+            let enq_job = enq_job.failed(error);
+
+            if !retryable || enq_job.retry_count().limit_reached(&policy) {
+                subscribers.publish(|| WorkerEvent::JobDead {
+                    name: name.0.clone(),
+                    id: enq_job.id().clone(),
+                    attempt,
+                    duration,
+                });
+                con.bury(enq_job)
+                    .expect("Failed to enqueue job into dead queue");
+                PerformJobOutput::JobDead
+            } else {
+                // Published before the job is put back into its queue, where another worker
+                // can pick it up right away
+                subscribers.publish(|| WorkerEvent::JobRetried {
+                    name: name.0.clone(),
+                    id: enq_job.id().clone(),
+                    attempt,
+                    duration,
+                });
+                con.retry(enq_job, &policy)
+                    .expect("Failed to enqueue job for retrying");
                 PerformJobOutput::JobRetried
             }
         }
     }
 }
 
+/// How performing a job went.
+#[derive(Debug)]
+enum Outcome {
+    Succeeded,
+    Failed {
+        /// The error the job failed with, as a string since errors can't be sent between
+        /// threads.
+        error: String,
+        retryable: bool,
+    },
+}
+
+fn perform<Q: JobQueue>(
+    job: &Job<Q>,
+    enq_job: &EnqueuedJob,
+    args: &Args,
+    con: &Connection<Q>,
+    policy: &RetryPolicy,
+) -> Outcome {
+    let job_result = panic::catch_unwind(AssertUnwindSafe(|| {
+        con.config()
+            .middleware
+            .perform(enq_job, args, &mut || job.perform(args, con))
+    })).unwrap_or_else(|payload| Err(Box::new(JobPanicked::from_payload(payload))));
+
+    match job_result {
+        Ok(()) => Outcome::Succeeded,
+        Err(err) => Outcome::Failed {
+            error: err.to_string(),
+            retryable: policy.should_retry(&*err),
+        },
+    }
+}
+
+/// Performs jobs that have a timeout on a separate thread, so the worker can stop waiting for
+/// them.
+///
+/// Threads can't be interrupted, so when a job times out its thread is left behind to finish on
+/// its own and a new thread is spawned for the next job.
+struct Executor<Q> {
+    spawn: Box<Fn() -> ExecutorThread<Q>>,
+    thread: Option<ExecutorThread<Q>>,
+}
+
+type ExecutorJob<Q> = (Box<Job<Q> + Send>, EnqueuedJob, Args, RetryPolicy);
+
+struct ExecutorThread<Q> {
+    jobs: Sender<ExecutorJob<Q>>,
+    outcomes: Receiver<Outcome>,
+}
+
+impl<Q> Executor<Q> {
+    fn new<F>(spawn: F) -> Executor<Q>
+    where
+        F: 'static + Fn() -> ExecutorThread<Q>,
+    {
+        Executor {
+            spawn: Box::new(spawn),
+            thread: None,
+        }
+    }
+
+    /// Perform a job, waiting at most `timeout` for it. Also returns `true` if the job timed out
+    /// and was left running on its thread.
+    fn perform(
+        &mut self,
+        job: Box<Job<Q> + Send>,
+        enq_job: &EnqueuedJob,
+        args: Args,
+        policy: RetryPolicy,
+        timeout: Duration,
+    ) -> (Outcome, bool) {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => (self.spawn)(),
+        };
+
+        if thread.jobs.send((job, enq_job.clone(), args, policy)).is_err() {
+            let outcome = Outcome::Failed {
+                error: "Job thread stopped unexpectedly".to_string(),
+                retryable: true,
+            };
+            return (outcome, false);
+        }
+
+        match thread.outcomes.recv_timeout(timeout) {
+            Ok(outcome) => {
+                self.thread = Some(thread);
+                (outcome, false)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                error!(
+                    "Job {} ({}) timed out after {:?}, replacing its thread",
+                    enq_job.name(),
+                    enq_job.id(),
+                    timeout
+                );
+                let err = JobTimedOut::new(timeout);
+                let outcome = Outcome::Failed {
+                    error: err.to_string(),
+                    retryable: policy.should_retry(&err),
+                };
+                (outcome, true)
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                let outcome = Outcome::Failed {
+                    error: "Job thread stopped unexpectedly".to_string(),
+                    retryable: true,
+                };
+                (outcome, false)
+            }
+        }
+    }
+}
+
+fn spawn_executor<Q, T, K>(config: Config, queue_config: K, lookup_job: T) -> ExecutorThread<Q>
+where
+    K: 'static + Clone + Send,
+    Q: 'static + JobQueue<Config = K>,
+    T: 'static + LookupJob<Q> + Send + Clone,
+{
+    let (job_sender, jobs) = channel::<ExecutorJob<Q>>();
+    let (outcome_sender, outcomes) = channel();
+
+    thread::spawn(move || {
+        let con =
+            establish(config, queue_config, lookup_job).expect("failed to establish connection");
+
+        for (job, enq_job, args, policy) in jobs {
+            let outcome = perform(&*job, &enq_job, &args, &con, &policy);
+            if outcome_sender.send(outcome).is_err() {
+                // The worker gave up waiting for the job, so its concurrency slot is only free
+                // now. If the job finished right as it timed out, the slot frees up once its
+                // lease runs out.
+                if let Some(ref concurrency) = job.options().concurrency {
+                    con.release_concurrency_slot(&job.name(), concurrency, &enq_job);
+                }
+                break;
+            }
+        }
+    });
+
+    ExecutorThread {
+        jobs: job_sender,
+        outcomes,
+    }
+}
+
 struct MultiplexChannel<T> {
     senders: Vec<Sender<T>>,
 }
@@ -93,6 +93,60 @@ robin_test!(retrying_jobs, || {
     assert_eq!(con.retry_queue_size().unwrap(), 0);
 });
 
+robin_test!(retried_jobs_wait_for_their_backoff, || {
+    use robin::queue_adapters::{JobQueue, QueueIdentifier};
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+    use std::time::{Duration, SystemTime};
+
+    static ATTEMPTS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    jobs! { TestJob(()) }
+
+    impl TestJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let backoff = Duration::from_secs(60 * 60);
+
+    let mut config = test_config();
+    config.retry_backoff = RetryBackoff::Constant(backoff);
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let enqueued_before = SystemTime::now();
+    TestJob::perform_later(&(), &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 1);
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.main_queue_scheduled_size().unwrap(), 1);
+
+    // The queue is asked about fixed times, so the test doesn't depend on how long it takes
+    let queue = MemoryQueue::new(&queue_config).unwrap();
+    let not_yet_due = enqueued_before + backoff - Duration::from_secs(60);
+    assert_eq!(queue.enqueue_due_jobs(QueueIdentifier::Main, not_yet_due).unwrap(), 0);
+    assert_eq!(con.main_queue_scheduled_size().unwrap(), 1);
+
+    let due = SystemTime::now() + backoff;
+    assert_eq!(queue.enqueue_due_jobs(QueueIdentifier::Main, due).unwrap(), 1);
+    assert_eq!(con.main_queue_size().unwrap(), 1);
+    assert_eq!(con.main_queue_scheduled_size().unwrap(), 0);
+});
+
 robin_test!(performing_with_in_memory_queue, || {
     use std::time::Duration;
 
@@ -117,10 +171,1508 @@ robin_test!(performing_with_in_memory_queue, || {
     TestJob::perform_later(&filename, &con).unwrap();
 
     robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &test_config(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+});
+
+robin_test!(scheduling_jobs, || {
+    use std::time::{Duration, SystemTime};
+
+    jobs! { TestJob(()) }
+
+    impl TestJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    let config = test_config();
+    let queue_config = test_redis_init();
+    let con = robin_establish_connection!(RedisQueue, config, queue_config).unwrap();
+
+    TestJob::perform_in(&(), Duration::from_secs(60 * 60), &con).unwrap();
+    TestJob::perform_at(&(), SystemTime::now() - Duration::from_secs(1), &con).unwrap();
+
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.main_queue_scheduled_size().unwrap(), 2);
+
+    assert_eq!(con.enqueue_due_jobs().unwrap(), 1);
+
+    assert_eq!(con.main_queue_size().unwrap(), 1);
+    assert_eq!(con.main_queue_scheduled_size().unwrap(), 1);
+});
+
+robin_test!(dead_jobs, || {
+    jobs! { TestJob(()) }
+
+    impl TestJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let mut config = test_config();
+    config.retry_count_limit = 2;
+    let queue_config = test_redis_init();
+    let con = robin_establish_connection!(RedisQueue, config, queue_config).unwrap();
+
+    TestJob::perform_later(&(), &con).unwrap();
+
+    robin::worker::spawn_workers::<RedisQueue, _, _>(
+        &config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.retry_queue_size().unwrap(), 0);
+    assert_eq!(con.dead_queue_size().unwrap(), 1);
+
+    let dead_jobs = con.dead_jobs().unwrap();
+    assert_eq!(dead_jobs[0].name(), "TestJob");
+    assert_eq!(dead_jobs[0].last_error(), Some("TestError(\"fail\")"));
+    assert!(dead_jobs[0].failed_at().is_some());
+
+    assert!(con.requeue_dead_job(&dead_jobs[0]).unwrap());
+    assert!(!con.requeue_dead_job(&dead_jobs[0]).unwrap());
+
+    assert_eq!(con.dead_queue_size().unwrap(), 0);
+    assert_eq!(con.main_queue_size().unwrap(), 1);
+});
+
+robin_test!(recovering_jobs_from_crashed_workers, || {
+    use robin::queue_adapters::QueueIdentifier;
+    use std::{thread, time::Duration};
+
+    jobs! { TestJob(()) }
+
+    impl TestJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    let config = test_config();
+    let mut queue_config = test_redis_init();
+    queue_config.reliable_fetch = true;
+    queue_config.orphan_timeout = 1;
+    let con = robin_establish_connection!(RedisQueue, config, queue_config).unwrap();
+
+    TestJob::perform_later(&(), &con).unwrap();
+
+    let crashed_worker = RedisQueue::new(&queue_config).unwrap();
+    crashed_worker.dequeue(&[QueueIdentifier::Main]).unwrap();
+
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.recover_orphaned_jobs().unwrap(), 0);
+
+    thread::sleep(Duration::from_secs(2));
+
+    assert_eq!(con.recover_orphaned_jobs().unwrap(), 1);
+    assert_eq!(con.main_queue_size().unwrap(), 1);
+
+    robin::worker::spawn_workers::<RedisQueue, _, _>(
         &config.clone(),
         queue_config.clone(),
         __robin_lookup_job,
     ).perform_all_jobs_and_die();
 
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.recover_orphaned_jobs().unwrap(), 0);
+});
+
+robin_test!(shutting_down_within_a_timeout, || {
+    use std::thread;
+    use std::time::Duration;
+
+    jobs! { SlowJob(u64) }
+
+    impl SlowJob {
+        fn perform<Q>(millis: u64, _con: &Connection<Q>) -> JobResult {
+            thread::sleep(Duration::from_millis(millis));
+            Ok(())
+        }
+    }
+
+    let mut config = test_config();
+    config.worker_count = 1;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let workers = robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    );
+    let shutdown = workers.die_within(Duration::from_secs(1));
+    assert_eq!(shutdown, Shutdown::Graceful);
+
+    SlowJob::perform_later(&3000, &con).unwrap();
+
+    let workers = robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    );
+    thread::sleep(Duration::from_millis(500));
+    let shutdown = workers.die_within(Duration::from_millis(100));
+    assert_eq!(shutdown, Shutdown::TimedOut);
+    assert_eq!(shutdown.exit_code(), 1);
+});
+
+robin_test!(quiet_workers_put_back_jobs_they_were_waiting_for, || {
+    use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+    use std::thread;
+    use std::time::Duration;
+
+    static PERFORMED: AtomicBool = ATOMIC_BOOL_INIT;
+
+    jobs! { TestJob(()) }
+
+    impl TestJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            PERFORMED.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    let mut config = test_config();
+    config.worker_count = 1;
+
+    let queue_config = MemoryQueueConfig::new(Duration::from_secs(1));
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let workers = robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    );
+    thread::sleep(Duration::from_millis(100));
+    workers.quiet();
+
+    TestJob::perform_later(&(), &con).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    assert!(!PERFORMED.load(Ordering::SeqCst));
+    assert_eq!(con.main_queue_size().unwrap(), 1);
+
+    workers.die();
+    assert!(!PERFORMED.load(Ordering::SeqCst));
+});
+
+robin_test!(jobs_that_panic_are_retried, || {
+    jobs! {
+        PanickingJob(()),
+        TestJob(String),
+    }
+
+    impl PanickingJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            panic!("boom")
+        }
+    }
+
+    impl TestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filename = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+    config.retry_count_limit = 1;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    PanickingJob::perform_later(&(), &con).unwrap();
+    TestJob::perform_later(&filename, &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+
+    let dead_jobs = con.dead_jobs().unwrap();
+    assert_eq!(dead_jobs.len(), 1);
+    assert_eq!(dead_jobs[0].last_error(), Some("Job panicked: boom"));
+});
+
+robin_test!(unknown_jobs_are_moved_to_the_dead_queue, || {
+    use robin::queue_adapters::{EnqueuedJob, QueueIdentifier, RetryCount};
+
+    jobs! { TestJob(String) }
+
+    impl TestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filename = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let queue = MemoryQueue::new(&queue_config).unwrap();
+    let unknown_job = EnqueuedJob::new("RenamedJob", "null", RetryCount::NeverRetried);
+    queue.enqueue(QueueIdentifier::Main, unknown_job).unwrap();
+    TestJob::perform_later(&filename, &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+
+    let dead_jobs = con.dead_jobs().unwrap();
+    assert_eq!(dead_jobs.len(), 1);
+    assert_eq!(dead_jobs[0].name(), "RenamedJob");
+    assert_eq!(dead_jobs[0].last_error(), Some("Unknown job \"RenamedJob\""));
+});
+
+robin_test!(jobs_with_malformed_args_are_poisoned, || {
+    use robin::queue_adapters::{EnqueuedJob, PoisonReason, QueueIdentifier, RetryCount};
+
+    jobs! { TestJob(String) }
+
+    impl TestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filename = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let queue = MemoryQueue::new(&queue_config).unwrap();
+    let malformed_job = EnqueuedJob::new("TestJob", "{not json", RetryCount::NeverRetried);
+    queue.enqueue(QueueIdentifier::Main, malformed_job).unwrap();
+    TestJob::perform_later(&filename, &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
     assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+    assert_eq!(con.dead_queue_size().unwrap(), 0);
+
+    let poisoned_jobs = con.poisoned_jobs().unwrap();
+    assert_eq!(poisoned_jobs.len(), 1);
+    assert_eq!(poisoned_jobs[0].reason(), PoisonReason::InvalidArgs);
+    assert!(poisoned_jobs[0].payload().contains("{not json"));
+
+    con.delete_all_poisoned().unwrap();
+    assert!(con.poisoned_jobs().unwrap().is_empty());
+});
+
+robin_test!(named_queues, || {
+    use robin::queue_adapters::QueueIdentifier;
+
+    jobs! {
+        ChargeCard(String) => "payments",
+        ExportReport(String) => "exports",
+    }
+
+    impl ChargeCard {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    impl ExportReport {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let payment = uuid();
+    let export = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+    config.queues = vec![
+        QueueSettings::new(QueueIdentifier::Named("payments")).weight(10),
+        QueueSettings::new(QueueIdentifier::Named("exports"))
+            .weight(0)
+            .workers(1),
+    ];
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    ChargeCard::perform_later(&payment, &con).unwrap();
+    ExportReport::perform_later(&export, &con).unwrap();
+
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.queue_size(QueueIdentifier::Named("payments")).unwrap(), 1);
+    assert_eq!(con.queue_size(QueueIdentifier::Named("exports")).unwrap(), 1);
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(read_tmp_test_file(payment.clone()).unwrap(), payment);
+    assert_eq!(read_tmp_test_file(export.clone()).unwrap(), export);
+    assert_eq!(con.queue_size(QueueIdentifier::Named("payments")).unwrap(), 0);
+    assert_eq!(con.queue_size(QueueIdentifier::Named("exports")).unwrap(), 0);
+});
+
+robin_test!(retrying_jobs_from_named_queues, || {
+    use robin::queue_adapters::QueueIdentifier;
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+    use std::thread;
+    use std::time::Duration;
+
+    static ATTEMPTS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    jobs! {
+        ExportReport(()) => "exports",
+    }
+
+    impl ExportReport {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let exports = QueueIdentifier::Named("exports");
+
+    let mut config = test_config();
+    config.retry_count_limit = 1;
+    config.retry_backoff = RetryBackoff::Constant(Duration::from_millis(200));
+    config.queues = vec![QueueSettings::new(exports).workers(1)];
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    ExportReport::perform_later(&(), &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 1);
+
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(con.enqueue_due_jobs().unwrap(), 1);
+    assert_eq!(con.queue_size(exports).unwrap(), 1);
+    assert_eq!(con.retry_queue_size().unwrap(), 0);
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+    assert_eq!(con.dead_queue_size().unwrap(), 1);
+
+    assert_eq!(con.requeue_all_dead_jobs().unwrap(), 1);
+    assert_eq!(con.queue_size(exports).unwrap(), 1);
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+});
+
+robin_test!(per_job_retry_policies, || {
+    use robin::queue_adapters::RetryCount;
+    use std::error::Error;
+
+    fn is_network_error(err: &(Error + 'static)) -> bool {
+        match err.downcast_ref::<TestError>() {
+            Some(err) => err.0 == "network",
+            None => false,
+        }
+    }
+
+    jobs! {
+        FailingJob(String) => {
+            retry: RetryPolicy::new(2)
+                .backoff(RetryBackoff::Immediate)
+                .retry_on(is_network_error),
+        },
+    }
+
+    impl FailingJob {
+        fn perform<Q>(error: String, _con: &Connection<Q>) -> JobResult {
+            if error == "network" {
+                TestError("network").into_job_result()
+            } else {
+                TestError("validation").into_job_result()
+            }
+        }
+    }
+
+    let mut config = test_config();
+    config.worker_count = 1;
+    config.retry_count_limit = 10;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    FailingJob::perform_later(&"validation".to_string(), &con).unwrap();
+    FailingJob::perform_later(&"network".to_string(), &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    let dead_jobs = con.dead_jobs().unwrap();
+    assert_eq!(dead_jobs.len(), 2);
+
+    let validation = dead_jobs
+        .iter()
+        .find(|job| job.args().contains("validation"))
+        .unwrap();
+    assert_eq!(validation.retry_count(), &RetryCount::Count(1));
+
+    let network = dead_jobs
+        .iter()
+        .find(|job| job.args().contains("network"))
+        .unwrap();
+    assert_eq!(network.retry_count(), &RetryCount::Count(3));
+});
+
+robin_test!(job_ids_and_enqueue_times, || {
+    jobs! { FailingJob(()) }
+
+    impl FailingJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let mut config = test_config();
+    config.worker_count = 1;
+    config.retry_count_limit = 1;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let id = FailingJob::perform_later(&(), &con).unwrap();
+    let other_id = FailingJob::perform_later(&(), &con).unwrap();
+    assert_ne!(id, other_id);
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    let dead_jobs = con.dead_jobs().unwrap();
+    let dead_job = dead_jobs.iter().find(|job| job.id() == &id).unwrap();
+    assert!(dead_job.first_enqueued_at() < dead_job.enqueued_at());
+});
+
+robin_test!(tracking_job_status, || {
+    use std::time::Duration;
+
+    jobs! {
+        TestJob(String),
+        FailingJob(()),
+    }
+
+    impl TestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    impl FailingJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let performed = uuid();
+    let cancelled = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+    config.retry_count_limit = 1;
+    config.job_status_ttl = Some(Duration::from_secs(60));
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let performed_id = TestJob::perform_later(&performed, &con).unwrap();
+    let cancelled_id = TestJob::perform_later(&cancelled, &con).unwrap();
+    let failing_id = FailingJob::perform_later(&(), &con).unwrap();
+
+    let status = con.job_status(&performed_id).unwrap().unwrap();
+    assert_eq!(status.state(), JobState::Enqueued);
+    assert!(status.attempts().is_empty());
+
+    assert!(con.cancel(&cancelled_id).unwrap());
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    let status = con.job_status(&performed_id).unwrap().unwrap();
+    assert_eq!(status.state(), JobState::Succeeded);
+    assert_eq!(status.attempts().len(), 1);
+
+    let status = con.job_status(&cancelled_id).unwrap().unwrap();
+    assert_eq!(status.state(), JobState::Cancelled);
+    assert!(status.attempts().is_empty());
+    assert!(read_tmp_test_file(cancelled.clone()).is_err());
+    assert!(!con.cancel(&cancelled_id).unwrap());
+
+    let status = con.job_status(&failing_id).unwrap().unwrap();
+    assert_eq!(status.state(), JobState::Dead);
+    assert_eq!(status.attempts().len(), 2);
+    assert_eq!(status.attempts()[0].error(), Some("TestError(\"fail\")"));
+});
+
+robin_test!(unique_jobs, || {
+    use std::time::Duration;
+
+    jobs! {
+        UniqueTestJob(String) => {
+            unique: UniqueJob::new(Duration::from_secs(60)),
+        },
+    }
+
+    impl UniqueTestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filename = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    // Performed first, when the identical job to its continuation is still queued
+    let chained = uuid();
+    UniqueTestJob::chain(&chained)
+        .then(&UniqueTestJob, &filename)
+        .perform_later(&con)
+        .unwrap();
+
+    let id = UniqueTestJob::perform_later(&filename, &con).unwrap();
+    let duplicate_id = UniqueTestJob::perform_later(&filename, &con).unwrap();
+    assert_eq!(id, duplicate_id);
+    assert_eq!(con.main_queue_size().unwrap(), 2);
+
+    let other_id = UniqueTestJob::perform_later(&uuid(), &con).unwrap();
+    assert_ne!(id, other_id);
+    assert_eq!(con.main_queue_size().unwrap(), 3);
+
+    let duplicate_chain = UniqueTestJob::chain(&filename)
+        .then(&UniqueTestJob, &uuid())
+        .perform_later(&con);
+    match duplicate_chain {
+        Err(robin::error::Error::DuplicateChain(existing_id)) => assert_eq!(existing_id, id),
+        other => panic!("Expected a duplicate chain error, got {:?}", other),
+    }
+    assert_eq!(con.main_queue_size().unwrap(), 3);
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+    assert_eq!(read_tmp_test_file(chained.clone()).unwrap(), chained);
+    // The job whose continuation was skipped succeeded rather than being retried
+    assert_eq!(con.main_queue_scheduled_size().unwrap(), 0);
+    assert_eq!(con.dead_jobs().unwrap().len(), 0);
+
+    let new_id = UniqueTestJob::perform_later(&filename, &con).unwrap();
+    assert_ne!(id, new_id);
+    assert_eq!(con.main_queue_size().unwrap(), 1);
+});
+
+robin_test!(enqueuing_many_jobs, || {
+    jobs! { TestJob(String) }
+
+    impl TestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filenames: Vec<String> = (0..10).map(|_| uuid()).collect();
+
+    let config = test_config();
+    let queue_config = test_redis_init();
+    let con = robin_establish_connection!(RedisQueue, config, queue_config).unwrap();
+
+    let ids = TestJob::perform_later_many(&filenames, &con).unwrap();
+    assert_eq!(ids.len(), filenames.len());
+    assert_eq!(con.main_queue_size().unwrap(), filenames.len());
+
+    robin::worker::spawn_workers::<RedisQueue, _, _>(
+        &config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    for filename in filenames {
+        assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+    }
+});
+
+robin_test!(batches, || {
+    jobs! {
+        BatchJob(bool),
+        SucceededCallback(String),
+        CompletedCallback(String),
+    }
+
+    impl BatchJob {
+        fn perform<Q>(succeed: bool, _con: &Connection<Q>) -> JobResult {
+            if succeed {
+                Ok(())
+            } else {
+                TestError("fail").into_job_result()
+            }
+        }
+    }
+
+    impl SucceededCallback {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    impl CompletedCallback {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let all_succeeded = uuid();
+    let all_completed = uuid();
+    let some_succeeded = uuid();
+    let some_completed = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 2;
+    config.retry_count_limit = 0;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let mut batch = con.batch();
+    batch.on_success(&SucceededCallback, &all_succeeded).unwrap();
+    batch.on_complete(&CompletedCallback, &all_completed).unwrap();
+    for _ in 0..5 {
+        BatchJob::add_to_batch(&true, &mut batch).unwrap();
+    }
+    let succeeding_batch = batch.run().unwrap();
+
+    let mut batch = con.batch();
+    batch.on_success(&SucceededCallback, &some_succeeded).unwrap();
+    batch.on_complete(&CompletedCallback, &some_completed).unwrap();
+    BatchJob::add_to_batch(&true, &mut batch).unwrap();
+    BatchJob::add_to_batch(&false, &mut batch).unwrap();
+    let failing_batch = batch.run().unwrap();
+
+    let status = con.batch_status(&succeeding_batch).unwrap().unwrap();
+    assert_eq!(status.total(), 5);
+    assert_eq!(status.pending(), 5);
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    let status = con.batch_status(&succeeding_batch).unwrap().unwrap();
+    assert!(status.is_finished());
+    assert_eq!(status.succeeded(), 5);
+    assert_eq!(read_tmp_test_file(all_succeeded.clone()).unwrap(), all_succeeded);
+    assert_eq!(read_tmp_test_file(all_completed.clone()).unwrap(), all_completed);
+
+    let status = con.batch_status(&failing_batch).unwrap().unwrap();
+    assert!(status.is_finished());
+    assert_eq!(status.succeeded(), 1);
+    assert_eq!(status.failed(), 1);
+    assert!(read_tmp_test_file(some_succeeded.clone()).is_err());
+    assert_eq!(read_tmp_test_file(some_completed.clone()).unwrap(), some_completed);
+});
+
+robin_test!(finishing_a_batch_job_twice_only_counts_it_once, || {
+    use robin::batch::{BatchCallback, BatchEvent};
+    use robin::queue_adapters::{EnqueuedJob, JobQueue, QueueIdentifier, RetryCount};
+    use std::time::Duration;
+
+    let queue_config = MemoryQueueConfig::default();
+    let queue = MemoryQueue::new(&queue_config).unwrap();
+
+    let id = BatchId::new();
+    let first = EnqueuedJob::new("BatchJob", "null", RetryCount::NeverRetried);
+    let second = EnqueuedJob::new("BatchJob", "null", RetryCount::NeverRetried);
+    let callback = EnqueuedJob::new("Callback", "null", RetryCount::NeverRetried);
+    queue
+        .create_batch(
+            &id,
+            &[first.id().clone(), second.id().clone()],
+            &[BatchCallback::new(BatchEvent::Complete, QueueIdentifier::Main, callback.clone())],
+        )
+        .unwrap();
+
+    let ttl = Duration::from_secs(60);
+    let (status, enqueued) = queue.finish_batch_job(&id, first.id(), true, ttl).unwrap().unwrap();
+    assert_eq!(status.pending(), 1);
+    assert!(enqueued.is_empty());
+    assert_eq!(queue.finish_batch_job(&id, first.id(), true, ttl).unwrap(), None);
+
+    let (status, enqueued) = queue.finish_batch_job(&id, second.id(), false, ttl).unwrap().unwrap();
+    assert!(status.is_finished());
+    assert_eq!(status.succeeded(), 1);
+    assert_eq!(status.failed(), 1);
+    assert_eq!(enqueued, vec![callback]);
+    assert_eq!(queue.finish_batch_job(&id, second.id(), false, ttl).unwrap(), None);
+    assert_eq!(queue.size(QueueIdentifier::Main).unwrap(), 1);
+});
+
+robin_test!(chaining_jobs, || {
+    jobs! {
+        AppendJob((String, String)),
+        FailingJob(()),
+    }
+
+    impl AppendJob {
+        fn perform<Q>(args: (String, String), _con: &Connection<Q>) -> JobResult {
+            let (filename, step) = args;
+            let contents = read_tmp_test_file(filename.clone()).unwrap_or_default();
+            write_tmp_test_file(filename, contents + &step);
+            Ok(())
+        }
+    }
+
+    impl FailingJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let pipeline = uuid();
+    let never_run = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+    config.retry_count_limit = 0;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    AppendJob::chain(&(pipeline.clone(), "download".to_string()))
+        .then(&AppendJob, &(pipeline.clone(), ",transcode".to_string()))
+        .then(&AppendJob, &(pipeline.clone(), ",notify".to_string()))
+        .perform_later(&con)
+        .unwrap();
+    assert_eq!(con.main_queue_size().unwrap(), 1);
+
+    FailingJob::chain(&())
+        .then(&AppendJob, &(never_run.clone(), "ran".to_string()))
+        .perform_later(&con)
+        .unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(
+        read_tmp_test_file(pipeline.clone()).unwrap(),
+        "download,transcode,notify"
+    );
+    assert!(read_tmp_test_file(never_run.clone()).is_err());
+    assert_eq!(con.dead_queue_size().unwrap(), 1);
+});
+
+robin_test!(periodic_jobs, || {
+    use robin::periodic::CatchUp;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    jobs! {
+        HourlyJob(()),
+        CatchingUpJob(()),
+    }
+
+    impl HourlyJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    impl CatchingUpJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    let mut config = test_config();
+    config
+        .periodic_jobs
+        .push(HourlyJob::periodic("0 0 * * * *", &()).unwrap());
+    config.periodic_jobs.push(
+        CatchingUpJob::periodic("0 0 * * * *", &())
+            .unwrap()
+            .catch_up(CatchUp::All),
+    );
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+    let other_process: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let midnight = UNIX_EPOCH + Duration::from_secs(60 * 60 * 24 * 365);
+    let hour = Duration::from_secs(60 * 60);
+
+    assert_eq!(con.enqueue_periodic_jobs(midnight).unwrap(), 0);
+    assert_eq!(con.enqueue_periodic_jobs(midnight + hour / 2).unwrap(), 0);
+
+    assert_eq!(con.enqueue_periodic_jobs(midnight + hour).unwrap(), 2);
+    assert_eq!(other_process.enqueue_periodic_jobs(midnight + hour).unwrap(), 0);
+
+    let after_downtime = midnight + hour * 4;
+    assert_eq!(other_process.enqueue_periodic_jobs(after_downtime).unwrap(), 1 + 3);
+    assert_eq!(con.enqueue_periodic_jobs(after_downtime).unwrap(), 0);
+
+    assert_eq!(con.main_queue_size().unwrap(), 2 + 4);
+});
+
+robin_test!(rate_limited_jobs, || {
+    jobs! {
+        RateLimitedJob(String) => {
+            rate_limit: RateLimit::per_hour(2),
+        },
+    }
+
+    impl RateLimitedJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filenames: Vec<String> = (0..3).map(|_| uuid()).collect();
+
+    let mut config = test_config();
+    config.worker_count = 2;
+    config.retry_count_limit = 0;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    RateLimitedJob::perform_later_many(&filenames, &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    let performed = filenames
+        .iter()
+        .filter(|filename| read_tmp_test_file(filename.clone()).is_ok())
+        .count();
+    assert_eq!(performed, 2);
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.main_queue_scheduled_size().unwrap(), 1);
+    assert_eq!(con.dead_queue_size().unwrap(), 0);
+});
+
+robin_test!(concurrency_limited_jobs, || {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT,
+                            ATOMIC_USIZE_INIT};
+    use std::thread;
+    use std::time::Duration;
+
+    static RUNNING: AtomicUsize = ATOMIC_USIZE_INIT;
+    static OVERLAPPED: AtomicBool = ATOMIC_BOOL_INIT;
+
+    jobs! {
+        LimitedJob(String) => {
+            concurrency: ConcurrencyLimit::new(1),
+        },
+    }
+
+    impl LimitedJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            if RUNNING.fetch_add(1, Ordering::SeqCst) > 0 {
+                OVERLAPPED.store(true, Ordering::SeqCst);
+            }
+            thread::sleep(Duration::from_millis(50));
+            RUNNING.fetch_sub(1, Ordering::SeqCst);
+
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filenames: Vec<String> = (0..4).map(|_| uuid()).collect();
+
+    let mut config = test_config();
+    config.worker_count = 4;
+    config.scheduled_poll_interval = Duration::from_millis(10);
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    LimitedJob::perform_later_many(&filenames, &con).unwrap();
+
+    let performed = || {
+        filenames
+            .iter()
+            .filter(|filename| read_tmp_test_file(filename.clone()).is_ok())
+            .count()
+    };
+
+    for _ in 0..20 {
+        robin::worker::spawn_workers::<MemoryQueue, _, _>(
+            &config,
+            queue_config.clone(),
+            __robin_lookup_job,
+        ).perform_all_jobs_and_die();
+
+        if performed() == filenames.len() {
+            break;
+        }
+
+        thread::sleep(Duration::from_millis(20));
+        con.enqueue_due_jobs().unwrap();
+    }
+
+    assert_eq!(performed(), filenames.len());
+    assert!(!OVERLAPPED.load(Ordering::SeqCst));
+    assert_eq!(con.dead_queue_size().unwrap(), 0);
+});
+
+robin_test!(jobs_that_time_out_are_moved_to_the_dead_queue, || {
+    use std::thread;
+    use std::time::Duration;
+
+    jobs! {
+        StuckJob(()) => {
+            timeout: Duration::from_millis(100),
+        },
+        TestJob(String),
+    }
+
+    impl StuckJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            thread::sleep(Duration::from_secs(2));
+            Ok(())
+        }
+    }
+
+    impl TestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    let filename = uuid();
+
+    let mut config = test_config();
+    config.worker_count = 1;
+    config.retry_count_limit = 0;
+    config.job_timeout = Some(Duration::from_secs(10));
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    StuckJob::perform_later(&(), &con).unwrap();
+    TestJob::perform_later(&filename, &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+    assert_eq!(con.main_queue_size().unwrap(), 0);
+    assert_eq!(con.dead_queue_size().unwrap(), 1);
+
+    let dead_jobs = con.dead_jobs().unwrap();
+    assert_eq!(dead_jobs[0].name(), "StuckJob");
+    assert_eq!(dead_jobs[0].last_error(), Some("Job timed out after 100ms"));
+});
+
+robin_test!(middleware, || {
+    use robin::queue_adapters::EnqueuedJob;
+
+    jobs! { TestJob(String) }
+
+    impl TestJob {
+        fn perform<Q>(unique_string: String, _con: &Connection<Q>) -> JobResult {
+            write_tmp_test_file(unique_string.clone(), unique_string);
+            Ok(())
+        }
+    }
+
+    struct TagTenant;
+
+    impl ClientMiddleware for TagTenant {
+        fn call(
+            &self,
+            job: EnqueuedJob,
+            _args: &Args,
+            next: &mut FnMut(EnqueuedJob) -> RobinResult<JobId>,
+        ) -> RobinResult<JobId> {
+            next(job.with_metadata("tenant", "acme"))
+        }
+    }
+
+    struct RecordTenant;
+
+    impl ServerMiddleware for RecordTenant {
+        fn call(
+            &self,
+            job: &EnqueuedJob,
+            _args: &Args,
+            next: &mut FnMut() -> JobResult,
+        ) -> JobResult {
+            let tenant = job.metadata("tenant").unwrap_or("none").to_string();
+            write_tmp_test_file(job.id().to_string(), tenant);
+            next()
+        }
+    }
+
+    let filename = uuid();
+
+    let mut config = test_config();
+    config.middleware = Middleware::new().client(TagTenant).server(RecordTenant);
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let id = TestJob::perform_later(&filename, &con).unwrap();
+
+    let mut batch = con.batch();
+    let batch_job_id = TestJob::add_to_batch(&uuid(), &mut batch).unwrap();
+    batch.run().unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    assert_eq!(read_tmp_test_file(filename.clone()).unwrap(), filename);
+    assert_eq!(read_tmp_test_file(id.to_string()).unwrap(), "acme");
+    assert_eq!(read_tmp_test_file(batch_job_id.to_string()).unwrap(), "acme");
+});
+
+robin_test!(prometheus_metrics, || {
+    use robin::metrics::PrometheusSink;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    jobs! {
+        TestJob(()),
+        FailingJob(()),
+    }
+
+    impl TestJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    impl FailingJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let prometheus = PrometheusSink::new();
+
+    let mut config = test_config();
+    config.retry_count_limit = 0;
+    config.metrics = Metrics::new().sink(prometheus.clone());
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    TestJob::perform_later_many(&[(), ()], &con).unwrap();
+    FailingJob::perform_later(&(), &con).unwrap();
+
+    robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).perform_all_jobs_and_die();
+
+    let metrics = prometheus.render();
+    assert!(metrics.contains("robin_jobs_enqueued_total{job=\"TestJob\",queue=\"main\"} 2\n"));
+    assert!(metrics.contains("robin_jobs_processed_total{job=\"TestJob\"} 2\n"));
+    assert!(metrics.contains("robin_jobs_processed_total{job=\"FailingJob\"} 1\n"));
+    assert!(metrics.contains("robin_jobs_failed_total{job=\"FailingJob\"} 1\n"));
+    assert!(metrics.contains("robin_jobs_dead_total{job=\"FailingJob\"} 1\n"));
+    assert!(metrics.contains("robin_job_duration_seconds_count{job=\"TestJob\"} 2\n"));
+    assert!(!metrics.contains("robin_jobs_failed_total{job=\"TestJob\"}"));
+
+    let addr = prometheus.serve("127.0.0.1:0").unwrap();
+    // A client that never sends its request doesn't hold up the others
+    let _idle = TcpStream::connect(addr).unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with(&metrics));
+});
+
+robin_test!(subscribing_to_worker_events, || {
+    use robin::worker::WorkerEvent;
+
+    jobs! {
+        TestJob(()),
+        FailingJob(()),
+    }
+
+    impl TestJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    impl FailingJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            TestError("fail").into_job_result()
+        }
+    }
+
+    let mut config = test_config();
+    config.retry_count_limit = 1;
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let manager = robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    );
+    let events = manager.subscribe();
+
+    let test_job_id = TestJob::perform_later(&(), &con).unwrap();
+    let failing_job_id = FailingJob::perform_later(&(), &con).unwrap();
+
+    manager.perform_all_jobs_and_die();
+
+    let events: Vec<WorkerEvent> = events.iter().collect();
+    let events_of = |job_id: &JobId| -> Vec<(&'static str, u32)> {
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                WorkerEvent::JobStarted {
+                    ref id, attempt, ..
+                } if id == job_id => Some(("started", attempt)),
+                WorkerEvent::JobSucceeded {
+                    ref id, attempt, ..
+                } if id == job_id => Some(("succeeded", attempt)),
+                WorkerEvent::JobFailed {
+                    ref id, attempt, ..
+                } if id == job_id => Some(("failed", attempt)),
+                WorkerEvent::JobRetried {
+                    ref id, attempt, ..
+                } if id == job_id => Some(("retried", attempt)),
+                WorkerEvent::JobDead {
+                    ref id, attempt, ..
+                } if id == job_id => Some(("dead", attempt)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        events_of(&test_job_id),
+        vec![("started", 1), ("succeeded", 1)]
+    );
+    assert_eq!(
+        events_of(&failing_job_id),
+        vec![
+            ("started", 1),
+            ("failed", 1),
+            ("retried", 1),
+            ("started", 2),
+            ("failed", 2),
+            ("dead", 2),
+        ]
+    );
+    assert!(events.contains(&WorkerEvent::WorkerIdle));
+});
+
+robin_test!(worker_heartbeats, || {
+    use std::process;
+    use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+    use std::thread;
+    use std::time::Duration;
+
+    static RELEASED: AtomicBool = ATOMIC_BOOL_INIT;
+
+    jobs! { SlowJob(()) }
+
+    impl SlowJob {
+        fn perform<Q>(_args: (), _con: &Connection<Q>) -> JobResult {
+            while !RELEASED.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(10));
+            }
+            Ok(())
+        }
+    }
+
+    let mut config = test_config();
+    config.worker_count = 2;
+    config.heartbeat_interval = Duration::from_millis(10);
+
+    let queue_config = MemoryQueueConfig::default();
+
+    let con: Connection<MemoryQueue> = robin::connection::establish(
+        config.clone(),
+        queue_config.clone(),
+        __robin_lookup_job,
+    ).unwrap();
+
+    let id = SlowJob::perform_later(&(), &con).unwrap();
+
+    let manager = robin::worker::spawn_workers::<MemoryQueue, _, _>(
+        &config,
+        queue_config.clone(),
+        __robin_lookup_job,
+    );
+
+    let mut workers = vec![];
+    for _ in 0..100 {
+        workers = con.workers().unwrap();
+        if workers.iter().any(|worker| !worker.jobs().is_empty()) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(workers.len(), 1);
+    assert_eq!(workers[0].pid(), process::id());
+    assert_eq!(workers[0].threads(), 3);
+    assert_eq!(workers[0].queues(), &["main".to_string(), "retry".to_string()]);
+    assert_eq!(workers[0].jobs().len(), 1);
+    assert_eq!(workers[0].jobs()[0].id(), &id);
+    assert_eq!(workers[0].jobs()[0].name(), "SlowJob");
+
+    RELEASED.store(true, Ordering::SeqCst);
+    manager.perform_all_jobs_and_die();
+
+    assert!(con.workers().unwrap().is_empty());
+});
+
+robin_test!(listing_jobs, || {
+    use robin::queue_adapters::QueueIdentifier;
+
+    jobs! {
+        SendEmail((u32, String)),
+        SendSms(u32),
+    }
+
+    impl SendEmail {
+        fn perform<Q>(_args: (u32, String), _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    impl SendSms {
+        fn perform<Q>(_args: u32, _con: &Connection<Q>) -> JobResult {
+            Ok(())
+        }
+    }
+
+    let config = test_config();
+    let queue_config = test_redis_init();
+    let con = robin_establish_connection!(RedisQueue, config, queue_config).unwrap();
+
+    let mut email_ids = vec![];
+    for user_id in 0..5 {
+        email_ids.push(SendEmail::perform_later(&(user_id, "Hi".to_string()), &con).unwrap());
+        SendSms::perform_later(&user_id, &con).unwrap();
+    }
+
+    let all = con.list_jobs(QueueIdentifier::Main, &JobQuery::new()).unwrap();
+    assert_eq!(all.len(), 10);
+    assert_eq!(all[0].id(), &email_ids[0]);
+
+    let page = con.list_jobs(QueueIdentifier::Main, &JobQuery::new().offset(2).limit(3))
+        .unwrap();
+    let page_ids: Vec<_> = page.iter().map(|job| job.id()).collect();
+    let expected_ids: Vec<_> = all[2..5].iter().map(|job| job.id()).collect();
+    assert_eq!(page_ids, expected_ids);
+
+    let emails = con.list_jobs(
+        QueueIdentifier::Main,
+        &JobQuery::new().name("SendEmail").offset(1).limit(2),
+    ).unwrap();
+    let ids: Vec<_> = emails.iter().map(|job| job.id().clone()).collect();
+    assert_eq!(ids, email_ids[1..3].to_vec());
+
+    let to_user = con.list_jobs(
+        QueueIdentifier::Main,
+        &JobQuery::new().name("SendEmail").args_match("/0", 3),
+    ).unwrap();
+    assert_eq!(to_user.len(), 1);
+    assert_eq!(to_user[0].id(), &email_ids[3]);
+
+    assert!(
+        con.list_jobs(QueueIdentifier::Retry, &JobQuery::new())
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(con.main_queue_size().unwrap(), 10);
 });
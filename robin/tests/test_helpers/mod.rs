@@ -14,7 +14,9 @@ pub fn setup() {
 pub fn teardown() {}
 
 pub fn test_config() -> Config {
-    Config::default()
+    let mut config = Config::default();
+    config.retry_backoff = RetryBackoff::Immediate;
+    config
 }
 
 pub fn test_redis_init() -> RedisConfig {